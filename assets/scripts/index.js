@@ -323,6 +323,48 @@ const cards = {
       },
     ],
   },
+  "stats-github": {
+    label: "Stats (GitHub)",
+    path: "stats/github",
+    options: [
+      {
+        id: "github-username",
+        label: "Select username",
+        query: "username",
+        type: "input",
+        value: DEFAULT_USERNAME,
+      },
+      selectThemeOption,
+    ],
+  },
+  "streak-github": {
+    label: "Streak (GitHub)",
+    path: "streak/github",
+    options: [
+      {
+        id: "github-username",
+        label: "Select username",
+        query: "username",
+        type: "input",
+        value: DEFAULT_USERNAME,
+      },
+      selectThemeOption,
+    ],
+  },
+  "streak-wakatime": {
+    label: "Streak (WakaTime)",
+    path: "streak/wakatime",
+    options: [
+      {
+        id: "wakatime-username",
+        label: "Select username",
+        query: "username",
+        type: "input",
+        value: "Toil",
+      },
+      selectThemeOption,
+    ],
+  },
   "pin-huggingface": {
     label: "Pin (Huggingface)",
     path: "pin/huggingface",
@@ -8,8 +8,9 @@ use reqwest::{
     header::{ACCEPT, AUTHORIZATION, USER_AGENT},
 };
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{Value, json};
 
+use crate::api::github_tokens::{GRAPHQL_RESOURCE, REST_RESOURCE, TOKEN_POOL};
 use crate::data::config::CONFIG;
 
 lazy_static! {
@@ -78,14 +79,25 @@ pub struct RepositoryNodeLanguages {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct RepositoryNode {
     pub name: String,
+    pub is_fork: bool,
     pub languages: RepositoryNodeLanguages,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageInfo {
+    pub has_next_page: bool,
+    pub end_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UserLanguagesRepositories {
     pub nodes: Vec<RepositoryNode>,
+    pub page_info: PageInfo,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -93,6 +105,51 @@ pub struct UserLanguages {
     pub repositories: UserLanguagesRepositories,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotalCount {
+    pub total_count: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsRepositoryNode {
+    pub stargazer_count: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsRepositories {
+    pub total_count: u32,
+    pub nodes: Vec<StatsRepositoryNode>,
+    pub page_info: PageInfo,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsContributions {
+    pub total_commit_contributions: u32,
+    pub total_pull_request_review_contributions: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserStats {
+    pub name: Option<String>,
+    pub login: String,
+    pub contributions_collection: StatsContributions,
+    pub repositories_contributed_to: TotalCount,
+    pub pull_requests: TotalCount,
+    pub issues: TotalCount,
+    pub followers: TotalCount,
+    pub repositories: StatsRepositories,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UserStars {
+    pub repositories: StatsRepositories,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OptionUserData<T> {
     // user or null if not found
@@ -177,6 +234,8 @@ pub enum RestResponse<T> {
 
 pub type ActivityResponse = GraphQLResponse<OptionUserData<UserActivity>>;
 pub type LanguagesResponse = GraphQLResponse<OptionUserData<UserLanguages>>;
+pub type StatsResponse = GraphQLResponse<OptionUserData<UserStats>>;
+pub type StarsResponse = GraphQLResponse<OptionUserData<UserStars>>;
 pub type GistResponse = GraphQLResponse<ViewerData<OptionGistData>>;
 pub type RepositoryResponse = RestResponse<Repository>;
 
@@ -190,79 +249,122 @@ pub fn get_headers() -> HeaderMap {
         USER_AGENT,
         HeaderValue::from_str(&CONFIG.user_agent).unwrap(),
     );
-    if !&CONFIG.github_token.is_empty() {
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&CONFIG.github_token).unwrap(),
-        );
-    }
 
     headers
 }
 
+// adds token with max rate limit budget, returns its id to update budget from response
+fn add_token(headers: &mut HeaderMap, resource: &str) -> Option<usize> {
+    let (token_id, token) = TOKEN_POOL.pick(resource)?;
+    headers.insert(AUTHORIZATION, HeaderValue::from_str(&token).unwrap());
+
+    Some(token_id)
+}
+
 pub async fn request_graphql<T: for<'de> Deserialize<'de>>(
-    graphql_query: &String,
+    graphql_query: &str,
+    variables: Value,
 ) -> Result<T, Error> {
-    let request_url = format!("https://api.github.com/graphql");
-    let headers = get_headers();
+    let request_url = &CONFIG.github_graphql_url;
+    let mut headers = get_headers();
+    let token_id = add_token(&mut headers, GRAPHQL_RESOURCE);
     let request_body = json!({
-        "query": graphql_query
+        "query": graphql_query,
+        "variables": variables,
     });
 
-    let data = REQ_CLIENT
-        .post(&request_url)
+    let res = REQ_CLIENT
+        .post(request_url)
         .headers(headers)
         .json(&request_body)
         .send()
-        .await?
-        .json::<T>()
         .await?;
+    if let Some(token_id) = token_id {
+        TOKEN_POOL.update(token_id, res.headers());
+    }
+
+    let data = res.json::<T>().await?;
 
     Ok(data)
 }
 
 pub async fn request_get_api<T: for<'de> Deserialize<'de>>(pathname: &str) -> Result<T, Error> {
-    let request_url = format!("https://api.github.com{pathname}");
+    let request_url = format!("{}{pathname}", CONFIG.github_api_url);
     let mut headers = get_headers();
     headers.insert(
         HeaderName::from_str("X-GitHub-Api-Version").unwrap(),
         HeaderValue::from_str("2022-11-28").unwrap(),
     );
+    let token_id = add_token(&mut headers, REST_RESOURCE);
 
-    let data = REQ_CLIENT
-        .get(&request_url)
-        .headers(headers)
-        .send()
-        .await?
-        .json::<T>()
-        .await?;
+    let res = REQ_CLIENT.get(&request_url).headers(headers).send().await?;
+    if let Some(token_id) = token_id {
+        TOKEN_POOL.update(token_id, res.headers());
+    }
+
+    let data = res.json::<T>().await?;
 
     Ok(data)
 }
 
-#[allow(dead_code)]
-pub async fn get_languages(username: &String) -> Result<LanguagesResponse, Error> {
-    let graphql_query = format!(
-        r###"query {{
-            user(login: "{username}") {{
-                repositories(ownerAffiliations: OWNER, isFork: false, first: 100, orderBy: {{field: STARGAZERS, direction: DESC}}) {{
-                    nodes {{
-                        name
-                        languages(first: 10, orderBy: {{field: SIZE, direction: DESC}}) {{
-                            edges {{
-                                size
-                                node {{
-                                    name
-                                }}
-                            }}
-                        }}
-                    }}
-                }}
-            }}
-        }}"###
-    );
+// 1-39 alphanumeric chars, hyphens or underscores (Enterprise Managed Users
+// and GHES logins), can't start with a hyphen
+pub fn is_valid_login(login: &str) -> bool {
+    !login.is_empty()
+        && login.len() <= 39
+        && !login.starts_with('-')
+        && login
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+pub fn is_valid_repo_name(repo_name: &str) -> bool {
+    !repo_name.is_empty()
+        && repo_name.len() <= 100
+        && repo_name != "."
+        && repo_name != ".."
+        && repo_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
 
-    request_graphql::<LanguagesResponse>(&graphql_query).await
+pub fn is_valid_gist_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 32 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// one page of 100 repositories after cursor, forks included
+pub async fn get_languages(
+    username: &String,
+    after: Option<&String>,
+) -> Result<LanguagesResponse, Error> {
+    let graphql_query = r###"query userLanguages($login: String!, $after: String) {
+        user(login: $login) {
+            repositories(ownerAffiliations: OWNER, first: 100, after: $after, orderBy: {field: STARGAZERS, direction: DESC}) {
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+                nodes {
+                    name
+                    isFork
+                    languages(first: 10, orderBy: {field: SIZE, direction: DESC}) {
+                        edges {
+                            size
+                            node {
+                                name
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }"###;
+
+    let variables = json!({
+        "login": username,
+        "after": after,
+    });
+    request_graphql::<LanguagesResponse>(graphql_query, variables).await
 }
 
 // max 365 days
@@ -271,34 +373,102 @@ pub async fn get_activity(
     start_date: &String,
     end_date: &String,
 ) -> Result<ActivityResponse, Error> {
-    let graphql_query = format!(
-        r###"query {{
-            user(login: "{username}") {{
-                createdAt
-                contributionsCollection(from: "{start_date}", to: "{end_date}") {{
-                    contributionCalendar {{
-                        totalContributions
-                        weeks {{
-                            contributionDays {{
-                                weekday
-                                date
-                                contributionCount
-                                color
-                            }}
-                        }}
-                        months {{
-                            name
-                            year
-                            firstDay
-                            totalWeeks
-                        }}
-                    }}
-                }}
-            }}
-        }}"###
-    );
-
-    request_graphql::<ActivityResponse>(&graphql_query).await
+    let graphql_query = r###"query userActivity($login: String!, $from: DateTime!, $to: DateTime!) {
+        user(login: $login) {
+            createdAt
+            contributionsCollection(from: $from, to: $to) {
+                contributionCalendar {
+                    totalContributions
+                    weeks {
+                        contributionDays {
+                            weekday
+                            date
+                            contributionCount
+                            color
+                        }
+                    }
+                    months {
+                        name
+                        year
+                        firstDay
+                        totalWeeks
+                    }
+                }
+            }
+        }
+    }"###;
+
+    let variables = json!({
+        "login": username,
+        "from": start_date,
+        "to": end_date,
+    });
+    request_graphql::<ActivityResponse>(graphql_query, variables).await
+}
+
+pub async fn get_stats(username: &String, start_date: &String) -> Result<StatsResponse, Error> {
+    let graphql_query = r###"query userStats($login: String!, $from: DateTime!) {
+        user(login: $login) {
+            name
+            login
+            contributionsCollection(from: $from) {
+                totalCommitContributions
+                totalPullRequestReviewContributions
+            }
+            repositoriesContributedTo(first: 1, contributionTypes: [COMMIT, ISSUE, PULL_REQUEST, REPOSITORY]) {
+                totalCount
+            }
+            pullRequests(first: 1) {
+                totalCount
+            }
+            issues {
+                totalCount
+            }
+            followers {
+                totalCount
+            }
+            repositories(ownerAffiliations: OWNER, first: 100, orderBy: {field: STARGAZERS, direction: DESC}) {
+                totalCount
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+                nodes {
+                    stargazerCount
+                }
+            }
+        }
+    }"###;
+
+    let variables = json!({
+        "login": username,
+        "from": start_date,
+    });
+    request_graphql::<StatsResponse>(graphql_query, variables).await
+}
+
+// next page of 100 repositories for stars count of stats
+pub async fn get_stars(username: &String, after: &String) -> Result<StarsResponse, Error> {
+    let graphql_query = r###"query userStars($login: String!, $after: String!) {
+        user(login: $login) {
+            repositories(ownerAffiliations: OWNER, first: 100, after: $after, orderBy: {field: STARGAZERS, direction: DESC}) {
+                totalCount
+                pageInfo {
+                    hasNextPage
+                    endCursor
+                }
+                nodes {
+                    stargazerCount
+                }
+            }
+        }
+    }"###;
+
+    let variables = json!({
+        "login": username,
+        "after": after,
+    });
+    request_graphql::<StarsResponse>(graphql_query, variables).await
 }
 
 pub async fn get_repo(username: &String, repo_name: &String) -> Result<RepositoryResponse, Error> {
@@ -307,29 +477,54 @@ pub async fn get_repo(username: &String, repo_name: &String) -> Result<Repositor
 }
 
 pub async fn get_gist(id: &String) -> Result<GistResponse, Error> {
-    let graphql_query = format!(
-        r###"query gistInfo {{
-            viewer {{
-                gist(name: "{id}") {{
-                    description
-                    owner {{
-                        login
-                    }}
-                    stargazerCount
-                    forks {{
-                        totalCount
-                    }}
-                    files {{
+    let graphql_query = r###"query gistInfo($name: String!) {
+        viewer {
+            gist(name: $name) {
+                description
+                owner {
+                    login
+                }
+                stargazerCount
+                forks {
+                    totalCount
+                }
+                files {
+                    name
+                    language {
                         name
-                        language {{
-                            name
-                        }}
-                        size
-                    }}
-                }}
-            }}
-        }}"###
-    );
+                    }
+                    size
+                }
+            }
+        }
+    }"###;
+
+    let variables = json!({ "name": id });
+    request_graphql::<GistResponse>(graphql_query, variables).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_login() {
+        assert!(is_valid_login("octocat"));
+        assert!(is_valid_login("maestro-TW"));
+        assert!(is_valid_login("octocat_corp"));
+        assert!(!is_valid_login(""));
+        assert!(!is_valid_login("-octocat"));
+        assert!(!is_valid_login("octo cat"));
+        assert!(!is_valid_login("../octocat"));
+        assert!(!is_valid_login(&"a".repeat(40)));
+    }
 
-    request_graphql::<GistResponse>(&graphql_query).await
+    #[test]
+    fn validates_repo_name() {
+        assert!(is_valid_repo_name("stats-cards"));
+        assert!(is_valid_repo_name("my_repo.rs"));
+        assert!(!is_valid_repo_name(""));
+        assert!(!is_valid_repo_name(".."));
+        assert!(!is_valid_repo_name("a/b"));
+    }
 }
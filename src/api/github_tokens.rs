@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+
+use crate::data::config::CONFIG;
+
+// graphql and rest api have separate rate limits
+pub const GRAPHQL_RESOURCE: &str = "graphql";
+pub const REST_RESOURCE: &str = "core";
+
+lazy_static! {
+    pub static ref TOKEN_POOL: TokenPool = TokenPool::new(&CONFIG.github_tokens);
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RateLimit {
+    limit: u32,
+    remaining: u32,
+    // unix seconds
+    reset: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenBudget {
+    // index of token in GITHUB_TOKENS, tokens itself aren't exposed
+    id: usize,
+    // resource -> rate limit, empty until first request with the token
+    rate_limits: HashMap<String, RateLimit>,
+}
+
+struct Token {
+    value: String,
+    rate_limits: Mutex<HashMap<String, RateLimit>>,
+}
+
+pub struct TokenPool {
+    tokens: Vec<Token>,
+}
+
+fn get_unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn get_header_num<T: std::str::FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+// requests left until reset, unused tokens are the healthiest
+fn get_available(rate_limit: Option<&RateLimit>, now: u64) -> u32 {
+    match rate_limit {
+        None => u32::MAX,
+        Some(rate_limit) if rate_limit.reset <= now => rate_limit.limit,
+        Some(rate_limit) => rate_limit.remaining,
+    }
+}
+
+impl TokenPool {
+    pub fn new(tokens: &[String]) -> Self {
+        let tokens = tokens
+            .iter()
+            .map(|token| Token {
+                value: token.clone(),
+                rate_limits: Mutex::new(HashMap::new()),
+            })
+            .collect();
+
+        Self { tokens }
+    }
+
+    // token id and value with max budget for resource, None if there are no tokens
+    pub fn pick(&self, resource: &str) -> Option<(usize, String)> {
+        let now = get_unix_now();
+        let (id, token) = self.tokens.iter().enumerate().max_by_key(|(_, token)| {
+            let rate_limits = token.rate_limits.lock().unwrap();
+            get_available(rate_limits.get(resource), now)
+        })?;
+
+        // reserve one request, so concurrent requests are spread between tokens
+        if let Some(rate_limit) = token.rate_limits.lock().unwrap().get_mut(resource)
+            && rate_limit.reset > now
+        {
+            rate_limit.remaining = rate_limit.remaining.saturating_sub(1);
+        }
+
+        Some((id, token.value.clone()))
+    }
+
+    // updates budget from X-RateLimit-* response headers
+    pub fn update(&self, id: usize, headers: &HeaderMap) {
+        let Some(token) = self.tokens.get(id) else {
+            return;
+        };
+
+        let (Some(limit), Some(remaining), Some(reset)) = (
+            get_header_num(headers, "x-ratelimit-limit"),
+            get_header_num(headers, "x-ratelimit-remaining"),
+            get_header_num(headers, "x-ratelimit-reset"),
+        ) else {
+            return;
+        };
+
+        let resource = headers
+            .get("x-ratelimit-resource")
+            .and_then(|resource| resource.to_str().ok())
+            .unwrap_or(REST_RESOURCE)
+            .to_string();
+        token.rate_limits.lock().unwrap().insert(
+            resource,
+            RateLimit {
+                limit,
+                remaining,
+                reset,
+            },
+        );
+    }
+
+    pub fn get_budgets(&self) -> Vec<TokenBudget> {
+        self.tokens
+            .iter()
+            .enumerate()
+            .map(|(id, token)| TokenBudget {
+                id,
+                rate_limits: token.rate_limits.lock().unwrap().clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_pool(count: usize) -> TokenPool {
+        let tokens: Vec<String> = (0..count).map(|id| format!("token-{id}")).collect();
+        TokenPool::new(&tokens)
+    }
+
+    fn create_headers(remaining: u32, reset: u64, resource: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", "5000".parse().unwrap());
+        headers.insert("x-ratelimit-remaining", remaining.into());
+        headers.insert("x-ratelimit-reset", reset.into());
+        headers.insert("x-ratelimit-resource", resource.parse().unwrap());
+        headers
+    }
+
+    fn get_remaining(pool: &TokenPool, id: usize, resource: &str) -> Option<u32> {
+        pool.get_budgets()[id]
+            .rate_limits
+            .get(resource)
+            .map(|rate_limit| rate_limit.remaining)
+    }
+
+    #[test]
+    fn empty_pool_has_no_token() {
+        assert_eq!(create_pool(0).pick(GRAPHQL_RESOURCE), None);
+    }
+
+    #[test]
+    fn picks_token_with_max_budget() {
+        let pool = create_pool(3);
+        let reset = get_unix_now() + 3600;
+        pool.update(0, &create_headers(100, reset, GRAPHQL_RESOURCE));
+        pool.update(1, &create_headers(4000, reset, GRAPHQL_RESOURCE));
+        pool.update(2, &create_headers(10, reset, GRAPHQL_RESOURCE));
+
+        assert_eq!(
+            pool.pick(GRAPHQL_RESOURCE),
+            Some((1, "token-1".to_string()))
+        );
+        // one request is reserved until response headers update it
+        assert_eq!(get_remaining(&pool, 1, GRAPHQL_RESOURCE), Some(3999));
+    }
+
+    #[test]
+    fn unused_token_is_picked_first() {
+        let pool = create_pool(2);
+        let reset = get_unix_now() + 3600;
+        pool.update(0, &create_headers(4999, reset, GRAPHQL_RESOURCE));
+
+        assert_eq!(pool.pick(GRAPHQL_RESOURCE).unwrap().0, 1);
+        assert_eq!(get_remaining(&pool, 1, GRAPHQL_RESOURCE), None);
+    }
+
+    #[test]
+    fn updates_budget_from_headers() {
+        let pool = create_pool(1);
+        let reset = get_unix_now() + 3600;
+        pool.update(0, &create_headers(42, reset, GRAPHQL_RESOURCE));
+        pool.update(0, &create_headers(7, reset, REST_RESOURCE));
+
+        assert_eq!(get_remaining(&pool, 0, GRAPHQL_RESOURCE), Some(42));
+        assert_eq!(get_remaining(&pool, 0, REST_RESOURCE), Some(7));
+
+        // responses without rate limit headers and unknown tokens are ignored
+        pool.update(0, &HeaderMap::new());
+        pool.update(5, &create_headers(1, reset, GRAPHQL_RESOURCE));
+        assert_eq!(get_remaining(&pool, 0, GRAPHQL_RESOURCE), Some(42));
+    }
+
+    #[test]
+    fn budget_is_reset_after_reset_time() {
+        let pool = create_pool(2);
+        let now = get_unix_now();
+        pool.update(0, &create_headers(0, now - 1, GRAPHQL_RESOURCE));
+        pool.update(1, &create_headers(4000, now + 3600, GRAPHQL_RESOURCE));
+
+        // full limit of reset token is more than remaining budget of the other
+        assert_eq!(pool.pick(GRAPHQL_RESOURCE).unwrap().0, 0);
+        assert_eq!(get_remaining(&pool, 0, GRAPHQL_RESOURCE), Some(0));
+    }
+
+    #[test]
+    fn exhausted_tokens_are_skipped() {
+        let pool = create_pool(3);
+        let reset = get_unix_now() + 3600;
+        pool.update(0, &create_headers(0, reset, GRAPHQL_RESOURCE));
+        pool.update(1, &create_headers(1, reset, GRAPHQL_RESOURCE));
+        pool.update(2, &create_headers(0, reset, GRAPHQL_RESOURCE));
+
+        assert_eq!(pool.pick(GRAPHQL_RESOURCE).unwrap().0, 1);
+        assert_eq!(get_remaining(&pool, 1, GRAPHQL_RESOURCE), Some(0));
+
+        // every token is exhausted, so request is sent anyway and fails with rate limit
+        assert!(pool.pick(GRAPHQL_RESOURCE).is_some());
+        for id in 0..3 {
+            assert_eq!(get_remaining(&pool, id, GRAPHQL_RESOURCE), Some(0));
+        }
+    }
+}
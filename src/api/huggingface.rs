@@ -124,6 +124,7 @@ pub enum PipelineTag {
 }
 
 impl PipelineTag {
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         match self {
             PipelineTag::TextClassification => "Text Classification".to_string(),
@@ -191,6 +192,8 @@ impl PipelineTag {
 
 pub_struct! { CardData {
     license: Option<String>,
+    // spaces only
+    short_description: Option<String>,
     tags: Option<Vec<String>>,
     task_categories: Option<Vec<String>>,
 }}
@@ -305,6 +308,7 @@ pub enum SpaceRuntimeHardware {
 }
 
 impl SpaceRuntimeHardware {
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         match self {
             SpaceRuntimeHardware::CpuUpgrade => "CPU Upgrade".to_string(),
@@ -357,6 +361,7 @@ pub struct Dataset {
     #[serde(flatten)]
     pub base: BaseData,
     pub downloads: u32,
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -366,6 +371,7 @@ pub struct ErrorResponse {
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum ModelResponse {
     Failed(ErrorResponse),
     Valid(Model),
@@ -373,6 +379,7 @@ pub enum ModelResponse {
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum DatasetResponse {
     Failed(ErrorResponse),
     Valid(Dataset),
@@ -380,6 +387,7 @@ pub enum DatasetResponse {
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum SpaceResponse {
     Failed(ErrorResponse),
     Valid(Space),
@@ -394,6 +402,24 @@ pub enum RepoData {
 }
 
 impl RepoData {
+    pub fn get_base(&self) -> &BaseData {
+        match self {
+            RepoData::Model(model) => &model.base,
+            RepoData::Dataset(dataset) => &dataset.base,
+            RepoData::Space(space) => &space.base,
+        }
+    }
+
+    pub fn get_description(&self) -> Option<String> {
+        let description = match self {
+            RepoData::Model(_) => None,
+            RepoData::Dataset(dataset) => dataset.description.clone(),
+            RepoData::Space(space) => space.base.card_data.short_description.clone(),
+        };
+
+        description.filter(|description| !description.trim().is_empty())
+    }
+
     pub fn get_id(&self) -> String {
         match self {
             RepoData::Model(model) => model.base.id.clone(),
@@ -458,7 +484,7 @@ pub fn get_headers() -> HeaderMap {
 }
 
 async fn request_intl<T: for<'de> serde::Deserialize<'de>>(pathname: &String) -> Result<T, Error> {
-    let request_url = format!("https://huggingface.co/api/{pathname}");
+    let request_url = format!("{}/{pathname}", CONFIG.huggingface_api_url);
     let headers = get_headers();
     let stats = REQ_CLIENT
         .get(&request_url)
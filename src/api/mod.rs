@@ -1,3 +1,4 @@
 pub mod github;
+pub mod github_tokens;
 pub mod huggingface;
 pub mod wakatime;
@@ -1,15 +1,14 @@
 use lazy_static::lazy_static;
-use reqwest::{Client, Error};
+use reqwest::{Client, Error, Request, RequestBuilder, header::AUTHORIZATION};
 use serde::{Deserialize, Serialize};
 
-use crate::pub_struct;
+use crate::{data::config::CONFIG, pub_struct};
 
 lazy_static! {
     static ref REQ_CLIENT: Client = Client::new();
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[allow(dead_code)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 pub enum StatsRange {
     #[serde(rename = "last_7_days")]
     Last7Days,
@@ -23,54 +22,78 @@ pub enum StatsRange {
     AllTime,
 }
 
+impl StatsRange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatsRange::Last7Days => "last_7_days",
+            StatsRange::Last30Days => "last_30_days",
+            StatsRange::Last6Months => "last_6_months",
+            StatsRange::LastYear => "last_year",
+            StatsRange::AllTime => "all_time",
+        }
+    }
+
+    pub fn get_title(&self) -> &'static str {
+        match self {
+            StatsRange::Last7Days => "Last 7 days",
+            StatsRange::Last30Days => "Last 30 days",
+            StatsRange::Last6Months => "Last 6 months",
+            StatsRange::LastYear => "Last year",
+            StatsRange::AllTime => "All time",
+        }
+    }
+}
+
 // same for editors, categories, languages
 pub_struct! { Entry {
     name: String,
     total_seconds: f64,
     percent: f32,
     digital: String,
-    decimal: String,
+    // not returned by Wakapi
+    decimal: Option<String>,
     text: String,
     hours: i32,
     minutes: i8,
 }}
 
+// Wakapi returns only part of WakaTime fields, so fields unused by cards are optional
 pub_struct! {  Stats {
-    id: String,
-    user_id: String,
-    range: StatsRange,
-    timeout: i32,
-    writes_only: bool,
-    holidays: i32,
-    status: String, // pending_update
-    human_readable_daily_average: String,
-    is_up_to_date: bool,
+    id: Option<String>,
+    user_id: Option<String>,
+    range: Option<StatsRange>,
+    timeout: Option<i32>,
+    writes_only: Option<bool>,
+    holidays: Option<i32>,
+    status: Option<String>, // pending_update
+    human_readable_daily_average: Option<String>,
+    is_up_to_date: Option<bool>,
     total_seconds: f64,
-    total_seconds_including_other_language: f64,
-    percent_calculated: i8,
-    days_minus_holidays: i32,
-    daily_average_including_other_language: f64,
-    human_readable_daily_average_including_other_language: String,
-    editors: Vec<Entry>,
-    is_up_to_date_pending_future: bool,
-    is_already_updating: bool,
-    categories: Vec<Entry>,
+    total_seconds_including_other_language: Option<f64>,
+    percent_calculated: Option<i8>,
+    days_minus_holidays: Option<i32>,
+    daily_average_including_other_language: Option<f64>,
+    human_readable_daily_average_including_other_language: Option<String>,
+    editors: Option<Vec<Entry>>,
+    is_up_to_date_pending_future: Option<bool>,
+    is_already_updating: Option<bool>,
+    categories: Option<Vec<Entry>>,
     languages: Vec<Entry>,
-    is_stuck: bool,
-    daily_average: f64,
-    human_readable_total_including_other_language: String,
-    days_including_holidays: i32,
-    operating_systems: Vec<Entry>,
-    human_readable_total: String,
-    is_cached: bool,
-    username: String,
-    is_including_today: bool,
-    human_readable_range: String,
-    is_coding_activity_visible: bool,
-    is_language_usage_visible: bool,
-    is_editor_usage_visible: bool,
-    is_category_usage_visible: bool,
-    is_os_usage_visible: bool,
+    is_stuck: Option<bool>,
+    daily_average: Option<f64>,
+    human_readable_total_including_other_language: Option<String>,
+    days_including_holidays: Option<i32>,
+    operating_systems: Option<Vec<Entry>>,
+    human_readable_total: Option<String>,
+    is_cached: Option<bool>,
+    username: Option<String>,
+    is_including_today: Option<bool>,
+    human_readable_range: Option<String>,
+    is_coding_activity_visible: Option<bool>,
+    is_language_usage_visible: Option<bool>,
+    is_editor_usage_visible: Option<bool>,
+    is_category_usage_visible: Option<bool>,
+    is_os_usage_visible: Option<bool>,
 }}
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -99,20 +122,208 @@ pub_struct! { PrivateStats {
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum StatsResponse {
     Failed(ErrorResponse),
     Valid(SuccessResponse<Stats>),
     NoData(SuccessResponse<PrivateStats>),
 }
 
-pub async fn get_stats(username: &String) -> Result<StatsResponse, Error> {
-    let request_url = format!("https://wakatime.com/api/v1/users/{username}/stats/all_time");
+pub_struct! { GrandTotal {
+    text: String,
+    total_seconds: f64,
+}}
+
+pub_struct! { SummaryRange {
+    date: String,
+}}
+
+// one summary per day
+pub_struct! { Summary {
+    grand_total: GrandTotal,
+    range: SummaryRange,
+}}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SummariesResponse {
+    Failed(ErrorResponse),
+    Valid(SuccessResponse<Vec<Summary>>),
+}
+
+fn strip_scheme(host: &str) -> &str {
+    host.strip_prefix("https://")
+        .or_else(|| host.strip_prefix("http://"))
+        .unwrap_or(host)
+        .trim_end_matches('/')
+}
+
+// WakaTime-compatible api url of allowed Wakapi instance, hosts without
+// scheme use https, e.g. wakapi.dev or http://wakapi.lan
+fn find_wakapi_api_url(allowed_hosts: &[String], host: &str) -> Option<String> {
+    let allowed_host = allowed_hosts
+        .iter()
+        .map(|allowed_host| allowed_host.trim_end_matches('/'))
+        .find(|allowed_host| strip_scheme(allowed_host) == strip_scheme(host))?;
+    let base_url = if strip_scheme(allowed_host) == allowed_host {
+        format!("https://{allowed_host}")
+    } else {
+        allowed_host.to_string()
+    };
+
+    Some(format!("{base_url}/api/compat/wakatime/v1"))
+}
+
+// None if host isn't listed in WAKAPI_HOSTS
+pub fn get_wakapi_api_url(host: &str) -> Option<String> {
+    find_wakapi_api_url(&CONFIG.wakapi_hosts, host)
+}
+
+// WakaTime api key of user from WAKATIME_API_KEYS
+pub fn get_api_key(username: &str) -> Option<String> {
+    CONFIG
+        .wakatime_api_keys
+        .get(&username.to_lowercase())
+        .cloned()
+}
+
+// 1-255 chars of Wakapi usernames, WakaTime usernames are a subset of it
+pub fn is_valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= 255
+        && username != "."
+        && username != ".."
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@'))
+}
+
+// with api key user's private stats are returned too
+fn get_request(request_url: &str, api_key: Option<&String>) -> RequestBuilder {
+    let request = REQ_CLIENT.get(request_url);
+    match api_key {
+        Some(api_key) => request.header(AUTHORIZATION, api_key),
+        None => request,
+    }
+}
+
+// {api_url}/users/{username}/{path}, username is encoded as one path segment
+fn get_user_request(
+    api_url: &str,
+    username: &str,
+    path: &[&str],
+    api_key: Option<&String>,
+) -> Result<Request, Error> {
+    let mut request = get_request(api_url, api_key).build()?;
+    if let Ok(mut segments) = request.url_mut().path_segments_mut() {
+        segments
+            .pop_if_empty()
+            .push("users")
+            .push(username)
+            .extend(path);
+    }
+
+    Ok(request)
+}
+
+pub async fn get_stats(
+    api_url: &str,
+    username: &str,
+    range: StatsRange,
+    api_key: Option<&String>,
+) -> Result<StatsResponse, Error> {
+    let request = get_user_request(api_url, username, &["stats", range.as_str()], api_key)?;
     let stats = REQ_CLIENT
-        .get(&request_url)
-        .send()
+        .execute(request)
         .await?
         .json::<StatsResponse>()
         .await?;
 
     Ok(stats)
 }
+
+pub async fn get_summaries(
+    api_url: &str,
+    username: &str,
+    start_date: &str,
+    end_date: &str,
+    api_key: Option<&String>,
+) -> Result<SummariesResponse, Error> {
+    let mut request = get_user_request(api_url, username, &["summaries"], api_key)?;
+    request
+        .url_mut()
+        .query_pairs_mut()
+        .append_pair("start", start_date)
+        .append_pair("end", end_date);
+    let summaries = REQ_CLIENT
+        .execute(request)
+        .await?
+        .json::<SummariesResponse>()
+        .await?;
+
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const API_URL: &str = "https://wakapi.dev/api/compat/wakatime/v1";
+
+    fn get_user_path(username: &str) -> String {
+        let request = get_user_request(API_URL, username, &["stats", "all_time"], None).unwrap();
+        request.url().path().to_string()
+    }
+
+    #[test]
+    fn username_is_one_path_segment() {
+        assert_eq!(
+            get_user_path("Toil"),
+            "/api/compat/wakatime/v1/users/Toil/stats/all_time"
+        );
+        assert_eq!(
+            get_user_path("../../../admin"),
+            "/api/compat/wakatime/v1/users/..%2F..%2F..%2Fadmin/stats/all_time"
+        );
+        assert_eq!(
+            get_user_path("a?b#c"),
+            "/api/compat/wakatime/v1/users/a%3Fb%23c/stats/all_time"
+        );
+    }
+
+    #[test]
+    fn wakapi_api_url_uses_scheme_of_allowed_host() {
+        let hosts = vec!["wakapi.dev".to_string(), "http://wakapi.lan/".to_string()];
+
+        assert_eq!(
+            find_wakapi_api_url(&hosts, "wakapi.dev").unwrap(),
+            "https://wakapi.dev/api/compat/wakatime/v1"
+        );
+        assert_eq!(
+            find_wakapi_api_url(&hosts, "wakapi.lan").unwrap(),
+            "http://wakapi.lan/api/compat/wakatime/v1"
+        );
+        assert_eq!(
+            find_wakapi_api_url(&hosts, "http://wakapi.lan").unwrap(),
+            "http://wakapi.lan/api/compat/wakatime/v1"
+        );
+        // scheme of param doesn't downgrade https hosts
+        assert_eq!(
+            find_wakapi_api_url(&hosts, "http://wakapi.dev").unwrap(),
+            "https://wakapi.dev/api/compat/wakatime/v1"
+        );
+        assert_eq!(find_wakapi_api_url(&hosts, "evil.com"), None);
+        assert_eq!(find_wakapi_api_url(&hosts, "wakapi.dev.evil.com"), None);
+    }
+
+    #[test]
+    fn validates_username() {
+        assert!(is_valid_username("Toil"));
+        assert!(is_valid_username("john.doe_42"));
+        assert!(!is_valid_username(""));
+        assert!(!is_valid_username(".."));
+        assert!(!is_valid_username("../admin"));
+        assert!(!is_valid_username("a b"));
+        assert!(!is_valid_username(&"a".repeat(256)));
+    }
+}
@@ -1,7 +1,18 @@
 use crate::data::theme::Theme;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use lazy_static::lazy_static;
 use serde::Deserialize;
-use std::{env, time::Duration};
+use std::{collections::HashMap, env, time::Duration};
+
+// cards with own cache ttl, e.g. CACHE_TTL_ACTIVITY=1800
+pub const CACHE_CARDS: [&str; 6] = [
+    "top_langs",
+    "activity",
+    "pin_github",
+    "pin_huggingface",
+    "streak",
+    "stats",
+];
 
 #[derive(Deserialize)]
 pub struct CargoConfig {
@@ -18,10 +29,108 @@ pub struct Config {
     pub hostname: String,
     pub port: u16,
     pub cache_ttl: Duration,
+    pub cache_capacity: u64,
+    // card -> cache ttl, cards without override use cache_ttl
+    pub card_cache_ttls: HashMap<&'static str, Duration>,
+    // directory of persistent cache, in-memory only if not set
+    pub cache_dir: Option<String>,
+    // max count of entries in persistent cache
+    pub cache_disk_capacity: u64,
+    // how long stale data is served while refreshing or when upstream fails
+    pub cache_max_stale: Duration,
     pub user_agent: String,
-    pub github_token: String,
+    // GITHUB_TOKEN and GITHUB_TOKENS, used in turn by rate limit budget
+    pub github_tokens: Vec<String>,
+    // base urls of upstream APIs without trailing slash
+    pub github_api_url: String,
+    pub github_graphql_url: String,
+    pub wakatime_api_url: String,
+    // lowercased username -> Authorization header with user's WakaTime api key
+    pub wakatime_api_keys: HashMap<String, String>,
+    // hosts of Wakapi instances allowed in host param
+    pub wakapi_hosts: Vec<String>,
+    pub huggingface_api_url: String,
     pub huggingface_token: String,
     pub default_theme: Theme,
+    // directory with fallback fonts for text measuring
+    pub fonts_dir: Option<String>,
+}
+
+// duration in seconds
+fn get_env_duration(name: &str) -> Option<Duration> {
+    env::var(name)
+        .ok()
+        .filter(|val| !val.is_empty())
+        .map(|val| Duration::from_secs(val.parse().unwrap()))
+}
+
+fn get_env_url(name: &str, default: &str) -> String {
+    env::var(name)
+        .ok()
+        .filter(|val| !val.is_empty())
+        .unwrap_or(default.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+// comma separated GITHUB_TOKENS, e.g. GITHUB_TOKENS=a,b,c
+fn get_github_tokens() -> Vec<String> {
+    let single_token = env::var("GITHUB_TOKEN").unwrap_or_default();
+    let tokens = env::var("GITHUB_TOKENS").unwrap_or_default();
+    let mut github_tokens: Vec<String> = Vec::new();
+    for token in std::iter::once(single_token.as_str()).chain(tokens.split(',')) {
+        let token = token.trim();
+        if token.is_empty() {
+            continue;
+        }
+
+        let bearer_token = format!("Bearer {token}");
+        if !github_tokens.contains(&bearer_token) {
+            github_tokens.push(bearer_token);
+        }
+    }
+
+    github_tokens
+}
+
+// comma separated username:api_key pairs, e.g. WAKATIME_API_KEYS=Toil:waka_xxx,user:waka_yyy
+fn get_wakatime_api_keys() -> HashMap<String, String> {
+    env::var("WAKATIME_API_KEYS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| {
+            let (username, api_key) = pair.split_once(':')?;
+            let (username, api_key) = (username.trim(), api_key.trim());
+            if username.is_empty() || api_key.is_empty() {
+                return None;
+            }
+
+            let auth_header = format!("Basic {}", BASE64.encode(api_key));
+            Some((username.to_lowercase(), auth_header))
+        })
+        .collect()
+}
+
+// comma separated WAKAPI_HOSTS, e.g. WAKAPI_HOSTS=wakapi.dev,http://wakapi.lan
+fn get_wakapi_hosts() -> Vec<String> {
+    env::var("WAKAPI_HOSTS")
+        .ok()
+        .filter(|val| !val.is_empty())
+        .unwrap_or("wakapi.dev".to_string())
+        .split(',')
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+fn get_card_cache_ttls() -> HashMap<&'static str, Duration> {
+    CACHE_CARDS
+        .iter()
+        .filter_map(|card| {
+            let env_name = format!("CACHE_TTL_{}", card.to_uppercase());
+            get_env_duration(&env_name).map(|ttl| (*card, ttl))
+        })
+        .collect()
 }
 
 lazy_static! {
@@ -37,20 +146,37 @@ lazy_static! {
             Ok(port) => port.parse().unwrap(),
             Err(_) => 7674,
         },
-        cache_ttl: Duration::from_secs(7200),
+        cache_ttl: get_env_duration("CACHE_TTL").unwrap_or(Duration::from_secs(7200)),
+        cache_capacity: env::var("CACHE_CAPACITY")
+            .ok()
+            .filter(|val| !val.is_empty())
+            .map(|val| val.parse().unwrap())
+            .unwrap_or(16384),
+        card_cache_ttls: get_card_cache_ttls(),
+        cache_dir: env::var("CACHE_DIR").ok().filter(|val| !val.is_empty()),
+        cache_disk_capacity: env::var("CACHE_DISK_CAPACITY")
+            .ok()
+            .filter(|val| !val.is_empty())
+            .map(|val| val.parse().unwrap())
+            .unwrap_or(65536),
+        cache_max_stale: get_env_duration("CACHE_MAX_STALE").unwrap_or(Duration::from_secs(86400)),
         user_agent:
             "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:135.0) Gecko/20100101 Firefox/135.0"
                 .to_string(),
-        github_token: std::env::var("GITHUB_TOKEN")
-            .ok()
-            .filter(|val| !val.is_empty())
-            .map(|val| format!("Bearer {val}"))
-            .unwrap_or_default(),
+        github_tokens: get_github_tokens(),
+        // for Github Enterprise Server: https://HOST/api/v3 and https://HOST/api/graphql
+        github_api_url: get_env_url("GITHUB_API_URL", "https://api.github.com"),
+        github_graphql_url: get_env_url("GITHUB_GRAPHQL_URL", "https://api.github.com/graphql"),
+        wakatime_api_url: get_env_url("WAKATIME_API_URL", "https://wakatime.com/api/v1"),
+        wakatime_api_keys: get_wakatime_api_keys(),
+        wakapi_hosts: get_wakapi_hosts(),
+        huggingface_api_url: get_env_url("HUGGINGFACE_API_URL", "https://huggingface.co/api"),
         huggingface_token: std::env::var("HUGGINGFACE_TOKEN")
             .ok()
             .filter(|val| !val.is_empty())
             .map(|val| format!("Bearer {val}"))
             .unwrap_or_default(),
         default_theme: Theme::CatppuccinMacchiato,
+        fonts_dir: env::var("FONTS_DIR").ok().filter(|val| !val.is_empty()),
     };
 }
@@ -5,11 +5,89 @@ lazy_static! {
     pub static ref LANG_TO_COLORS: HashMap<String, String> =
         serde_json::from_str(include_str!("../../data/lang2hex.json")).unwrap();
     pub static ref DEFAULT_LANG_COLOR: String = "#818181".to_string();
+    // lowercased name -> properly cased name of known languages
+    pub static ref LANG_NAMES: HashMap<String, String> =
+        serde_json::from_str::<Vec<String>>(include_str!("../../data/langnames.json"))
+            .unwrap()
+            .into_iter()
+            .map(|name| (name.to_lowercase(), name))
+            .collect();
+    // lowercased alias -> language name, same language is named differently
+    // by Github, Wakatime and users in params
+    pub static ref LANG_ALIASES: HashMap<&'static str, &'static str> = HashMap::from([
+        ("js", "JavaScript"),
+        ("ts", "TypeScript"),
+        ("py", "Python"),
+        ("rs", "Rust"),
+        ("rb", "Ruby"),
+        ("kt", "Kotlin"),
+        ("golang", "Go"),
+        ("cpp", "C++"),
+        ("cs", "C#"),
+        ("csharp", "C#"),
+        ("sh", "Shell"),
+        ("ps1", "PowerShell"),
+        ("pwsh", "PowerShell"),
+        ("vue.js", "Vue"),
+        ("jupyter", "Jupyter Notebook"),
+        ("ipynb", "Jupyter Notebook"),
+        ("dockerfile", "Docker"),
+        ("make", "Makefile"),
+        ("yml", "YAML"),
+        ("md", "Markdown"),
+        ("tex", "LaTeX"),
+        ("viml", "Vim Script"),
+        ("vimscript", "Vim Script"),
+        ("elisp", "Emacs Lisp"),
+    ]);
 }
 
-pub fn get_lang_color(lang_name: &String) -> String {
-    match LANG_TO_COLORS.get(&lang_name.to_lowercase()) {
+// lowercased language name with resolved alias, e.g. Vue.js -> vue
+pub fn normalize_lang_name(lang_name: &str) -> String {
+    let name = lang_name.trim().to_lowercase();
+    match LANG_ALIASES.get(name.as_str()) {
+        None => name,
+        Some(alias) => alias.to_lowercase(),
+    }
+}
+
+// properly cased name of known language, e.g. js -> JavaScript
+pub fn get_canonical_lang_name(lang_name: &str) -> Option<&'static str> {
+    LANG_NAMES
+        .get(&normalize_lang_name(lang_name))
+        .map(|name| name.as_str())
+}
+
+pub fn get_lang_color(lang_name: &str) -> String {
+    let color = LANG_TO_COLORS
+        .get(&lang_name.to_lowercase())
+        .or_else(|| LANG_TO_COLORS.get(&normalize_lang_name(lang_name)));
+    match color {
         None => DEFAULT_LANG_COLOR.clone(),
         Some(color) => color.clone(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_languages_have_canonical_names() {
+        let aliases = LANG_ALIASES.keys().map(|alias| alias.to_string());
+        for lang_name in LANG_TO_COLORS.keys().cloned().chain(aliases) {
+            assert!(
+                get_canonical_lang_name(&lang_name).is_some(),
+                "{lang_name} has no canonical name"
+            );
+        }
+    }
+
+    #[test]
+    fn canonical_names_are_properly_cased() {
+        assert_eq!(get_canonical_lang_name("css"), Some("CSS"));
+        assert_eq!(get_canonical_lang_name("HTML"), Some("HTML"));
+        assert_eq!(get_canonical_lang_name(" js "), Some("JavaScript"));
+        assert_eq!(get_canonical_lang_name("Frontend"), None);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod config;
 pub mod language;
 pub mod theme;
+pub mod tool;
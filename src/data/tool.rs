@@ -0,0 +1,17 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+use crate::data::language::DEFAULT_LANG_COLOR;
+
+lazy_static! {
+    // colors of Wakatime editors, operating systems and categories
+    pub static ref TOOL_TO_COLORS: HashMap<String, String> =
+        serde_json::from_str(include_str!("../../data/tool2hex.json")).unwrap();
+}
+
+pub fn get_tool_color(tool_name: &str) -> String {
+    match TOOL_TO_COLORS.get(&tool_name.to_lowercase()) {
+        None => DEFAULT_LANG_COLOR.clone(),
+        Some(color) => color.clone(),
+    }
+}
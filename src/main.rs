@@ -5,19 +5,34 @@ mod routes;
 mod templates;
 mod utils;
 
-use axum::{Router, routing::get};
+use std::sync::Arc;
+
+use axum::{Router, body::Bytes, middleware, routing::get};
 use dotenv::dotenv;
 use moka::future::Cache;
 use tower_http::services::ServeDir;
 
 use crate::data::config::CONFIG;
+use crate::utils::cache::{CacheStore, CardCache, DiskStore};
+
+const PNG_CACHE_CAPACITY: u64 = 32 * 1024 * 1024;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    let cache: Cache<String, String> = Cache::builder()
+    let cache_store: Option<Arc<dyn CacheStore>> = CONFIG.cache_dir.as_ref().map(|dir| {
+        let store = DiskStore::open(dir, CONFIG.cache_disk_capacity)
+            .unwrap_or_else(|err| panic!("Failed to open disk cache in {dir}: {err}"));
+        let store = Arc::new(store);
+        DiskStore::spawn_sweeper(store.clone());
+        store as Arc<dyn CacheStore>
+    });
+    let cache = CardCache::new(cache_store);
+    // rasterized cards are cached separately from svg, capacity is in bytes
+    let png_cache: Cache<String, Bytes> = Cache::builder()
         .time_to_live(CONFIG.cache_ttl)
-        .max_capacity(16384)
+        .weigher(|_, png_data: &Bytes| png_data.len().try_into().unwrap_or(u32::MAX))
+        .max_capacity(PNG_CACHE_CAPACITY)
         .build();
 
     let app = Router::new()
@@ -27,6 +42,22 @@ async fn main() {
             "/v1/top-langs/wakatime",
             get(routes::languages::get_waka_top_langs),
         )
+        .route(
+            "/v1/top-langs/wakapi",
+            get(routes::languages::get_wakapi_top_langs),
+        )
+        .route(
+            "/v1/top-editors/wakatime",
+            get(routes::languages::get_waka_top_editors),
+        )
+        .route(
+            "/v1/top-os/wakatime",
+            get(routes::languages::get_waka_top_os),
+        )
+        .route(
+            "/v1/top-categories/wakatime",
+            get(routes::languages::get_waka_top_categories),
+        )
         .route(
             "/v1/top-langs/github",
             get(routes::languages::get_github_top_langs),
@@ -38,7 +69,15 @@ async fn main() {
         .route("/v1/pin/huggingface", get(routes::pin::get_huggingface_pin))
         .route("/v1/pin/github", get(routes::pin::get_github_repo_pin))
         .route("/v1/pin/gist", get(routes::pin::get_github_gist_pin))
+        .route("/v1/streak/github", get(routes::streak::get_github_streak))
+        .route("/v1/streak/wakatime", get(routes::streak::get_waka_streak))
+        .route("/v1/stats/github", get(routes::stats::get_github_stats))
         .route("/v1/health", get(routes::health::get_health))
+        .route_layer(middleware::from_fn_with_state(
+            png_cache,
+            templates::render_format,
+        ))
+        .route_layer(middleware::from_fn(templates::with_etag))
         .with_state(cache);
 
     let listener = tokio::net::TcpListener::bind(format!("{0}:{1}", CONFIG.hostname, CONFIG.port))
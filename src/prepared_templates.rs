@@ -2,24 +2,76 @@ use crate::{
     api::{
         github::ErrorResponse as GHErrorResponse, huggingface::ErrorResponse as HFErrorResponse,
     },
-    templates::{ErrorTemplate, SVGTemplate},
+    templates::{ERROR_CACHE_CONTROL, ErrorTemplate, SVGTemplate},
 };
 
-use axum::response::{IntoResponse, Response};
+use axum::{
+    Json,
+    http::{StatusCode, header::CACHE_CONTROL},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::time::Duration;
+
+#[derive(Debug, Serialize)]
+pub struct ErrorData {
+    code: &'static str,
+    message: String,
+}
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    error: ErrorData,
+}
+
+#[derive(Debug, Clone)]
 pub enum PreparedTemplate {
     FailedFindUser,
     FailedFindRepo,
     FailedFindLanguages,
+    FailedFindActivity,
+    FailedFindStats,
+    HostNotAllowed,
+    APIKeyRequired,
     BadCredentials,
     APIRateLimit,
     Unknown,
 }
 
 impl PreparedTemplate {
-    pub fn render(&self) -> Response {
-        let template = match self {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PreparedTemplate::FailedFindUser => "user_not_found",
+            PreparedTemplate::FailedFindRepo => "repo_not_found",
+            PreparedTemplate::FailedFindLanguages => "languages_not_found",
+            PreparedTemplate::FailedFindActivity => "activity_not_found",
+            PreparedTemplate::FailedFindStats => "stats_not_found",
+            PreparedTemplate::HostNotAllowed => "host_not_allowed",
+            PreparedTemplate::APIKeyRequired => "api_key_required",
+            PreparedTemplate::BadCredentials => "bad_credentials",
+            PreparedTemplate::APIRateLimit => "rate_limited",
+            PreparedTemplate::Unknown => "unknown",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            PreparedTemplate::FailedFindUser
+            | PreparedTemplate::FailedFindRepo
+            | PreparedTemplate::FailedFindLanguages
+            | PreparedTemplate::FailedFindActivity
+            | PreparedTemplate::FailedFindStats => StatusCode::NOT_FOUND,
+            PreparedTemplate::HostNotAllowed => StatusCode::BAD_REQUEST,
+            PreparedTemplate::APIKeyRequired => StatusCode::FORBIDDEN,
+            // service token is broken, not the client request
+            PreparedTemplate::BadCredentials => StatusCode::INTERNAL_SERVER_ERROR,
+            PreparedTemplate::APIRateLimit => StatusCode::SERVICE_UNAVAILABLE,
+            PreparedTemplate::Unknown => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    pub fn get_template(&self) -> ErrorTemplate {
+        match self {
             PreparedTemplate::FailedFindUser => ErrorTemplate {
                 first_line: "Failed to find a user.",
                 second_line: "Check if it’s spelled correctly",
@@ -32,6 +84,22 @@ impl PreparedTemplate {
                 first_line: "Failed to find a user languages.",
                 second_line: "Maybe he's inactive",
             },
+            PreparedTemplate::FailedFindActivity => ErrorTemplate {
+                first_line: "Failed to find a user activity.",
+                second_line: "Maybe his stats are private",
+            },
+            PreparedTemplate::FailedFindStats => ErrorTemplate {
+                first_line: "Failed to find a user stats.",
+                second_line: "Maybe his stats are private",
+            },
+            PreparedTemplate::HostNotAllowed => ErrorTemplate {
+                first_line: "Host isn't allowed.",
+                second_line: "Check WAKAPI_HOSTS of the service",
+            },
+            PreparedTemplate::APIKeyRequired => ErrorTemplate {
+                first_line: "Wakatime API key required.",
+                second_line: "Add it to WAKATIME_API_KEYS",
+            },
             PreparedTemplate::BadCredentials => ErrorTemplate {
                 first_line: "Bad credentials.",
                 second_line: "Problems with service API token",
@@ -44,10 +112,36 @@ impl PreparedTemplate {
                 first_line: "Unknown API error.",
                 second_line: "Let us know about it",
             },
+        }
+    }
+
+    pub fn render(&self) -> Response {
+        let template = self.get_template();
+        // max-age is replaced with no-store below
+        let svg_template = SVGTemplate(template, Duration::ZERO);
+        (
+            self.status(),
+            [(CACHE_CONTROL, ERROR_CACHE_CONTROL)],
+            SVGTemplate::<ErrorTemplate>::into_response(svg_template),
+        )
+            .into_response()
+    }
+
+    pub fn render_json(&self) -> Response {
+        let template = self.get_template();
+        let body = ErrorBody {
+            error: ErrorData {
+                code: self.code(),
+                message: format!("{} {}", template.first_line, template.second_line),
+            },
         };
 
-        let svg_template = SVGTemplate(template);
-        return SVGTemplate::<ErrorTemplate>::into_response(svg_template);
+        (
+            self.status(),
+            [(CACHE_CONTROL, ERROR_CACHE_CONTROL)],
+            Json(body),
+        )
+            .into_response()
     }
 }
 
@@ -61,6 +155,15 @@ pub fn gh_handle_error_template(err: GHErrorResponse) -> PreparedTemplate {
     }
 }
 
+// REST API answers a missing repo or its owner with "Not Found"
+pub fn gh_repo_handle_error_template(err: GHErrorResponse) -> PreparedTemplate {
+    if err.message == "Not Found" {
+        PreparedTemplate::FailedFindRepo
+    } else {
+        gh_handle_error_template(err)
+    }
+}
+
 pub fn hf_handle_error_template(err: HFErrorResponse) -> PreparedTemplate {
     match err.error.as_str() {
         "Invalid credentials in Authorization header" => PreparedTemplate::BadCredentials,
@@ -4,7 +4,8 @@ use crate::api::github::{
 use crate::data::config::CONFIG;
 use crate::data::theme::{ActivityColor, Theme, ThemeData};
 use crate::prepared_templates::{PreparedTemplate, gh_handle_error_template};
-use crate::templates;
+use crate::templates::{self, Format};
+use crate::utils::cache::{Cached, CardCache};
 
 use askama::Template;
 use axum::{
@@ -12,7 +13,6 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use chrono::{Duration, Utc};
-use moka::future::Cache;
 use serde::{Deserialize, Serialize};
 
 const DAY_BLOCK_SIZE: i32 = 16;
@@ -25,6 +25,7 @@ pub struct Params {
     theme: Option<Theme>,
     period: Option<String>,
     with_title: Option<bool>,
+    format: Option<Format>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -45,14 +46,28 @@ pub struct ActivityMonth {
     weeks: Vec<ActivityWeek>,
 }
 
+#[derive(Debug)]
+pub struct ActivityDayBlock {
+    x: i32,
+    y: i32,
+    color: String,
+}
+
+#[derive(Debug)]
+pub struct ActivityLegend {
+    x: i32,
+    y: i32,
+    name: String,
+}
+
 #[derive(Template)]
-#[template(path = "compact/activity.html")]
+#[template(path = "compact/activity.html", escape = "svg")]
 pub struct CompactActivityTemplate {
     name: String,
     theme_data: ThemeData,
-    stats_data: String,
-    months_legend: String,
-    week_legend: String,
+    days: Vec<ActivityDayBlock>,
+    months_legend: Vec<ActivityLegend>,
+    week_legend: Vec<ActivityLegend>,
     width: u32,
     height: u32,
     with_title: bool,
@@ -75,21 +90,10 @@ impl Period {
     }
 }
 
-async fn get_activity_github_intl(
-    cache: Cache<String, String>,
-    username: &String,
-    period: &String,
+async fn activity_github_get_data(
+    username: String,
+    period: String,
 ) -> Result<Vec<ActivityMonth>, PreparedTemplate> {
-    if username.is_empty() {
-        return Err(PreparedTemplate::FailedFindUser);
-    }
-
-    let cache_key = format!("github:activity:{username}:{period}");
-    if let Some(cached) = cache.get(&cache_key).await {
-        let langs = serde_json::from_str(&cached).unwrap();
-        return Ok(langs);
-    }
-
     let offset_count = match Period::from_key(period.as_str()) {
         Some(offset) => offset,
         None => Period::SixMonths as u32,
@@ -97,8 +101,8 @@ async fn get_activity_github_intl(
     let offset: chrono::TimeDelta = Duration::days(offset_count as i64);
     let end_date = Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
     let start_date = (Utc::now() - offset).to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
-    let stats = github::get_activity(username, &start_date, &end_date).await;
-    if !stats.is_ok() {
+    let stats = github::get_activity(&username, &start_date, &end_date).await;
+    if stats.is_err() {
         return Err(PreparedTemplate::Unknown);
     }
 
@@ -166,32 +170,50 @@ async fn get_activity_github_intl(
         activity.push(month_data.clone());
     }
 
-    let cache_body = serde_json::to_string(&activity).unwrap();
-    cache.insert(cache_key, cache_body).await;
-
     Ok(activity)
 }
 
+async fn get_activity_github_intl(
+    cache: CardCache,
+    username: &String,
+    period: &String,
+) -> Result<Cached<Vec<ActivityMonth>>, PreparedTemplate> {
+    if !github::is_valid_login(username) {
+        return Err(PreparedTemplate::FailedFindUser);
+    }
+
+    let cache_key = format!("github:activity:{username}:{period}");
+    cache
+        .get_or_fetch(
+            cache_key,
+            activity_github_get_data(username.clone(), period.clone()),
+        )
+        .await
+}
+
 pub fn render_activity(
     username: String,
     with_title: bool,
     theme: Theme,
-    activity_res: Result<Vec<ActivityMonth>, PreparedTemplate>,
+    activity_res: Result<Cached<Vec<ActivityMonth>>, PreparedTemplate>,
 ) -> Response {
-    if !activity_res.is_ok() {
-        return activity_res.unwrap_err().render();
-    }
+    let Cached {
+        data: stats,
+        max_age,
+    } = match activity_res {
+        Ok(stats) => stats,
+        Err(err) => return err.render(),
+    };
 
     let theme_data = theme.get_data();
-    let stats = activity_res.unwrap();
     let first_day = stats
-        .get(0)
+        .first()
         .unwrap()
         .weeks
-        .get(0)
+        .first()
         .unwrap()
         .days
-        .get(0)
+        .first()
         .unwrap();
 
     let block_default_y = if with_title { 67 } else { 35 };
@@ -203,72 +225,80 @@ pub fn render_activity(
     let mut last_day_x = day_start_x;
     let mut day_start_y = block_default_y + (DAY_BLOCK_SIZE * (first_day.weekday as i32));
     let mut months_start_x = DEFAULT_START_X;
-    let mut months_legend: Vec<String> = Vec::new();
+    let mut months_legend: Vec<ActivityLegend> = Vec::new();
     let mut month_has_one_week = false;
-
-    let stats_data: Vec<String> = stats.iter().map(|stat| {
-        let week_els: Vec<String> = stat.weeks.iter().map(|week| {
-            let day_els: Vec<String> = week.days.iter().map(|day| {
-                last_day_x = day_start_x;
-                let day_color = match ActivityColor::from_key(&day.color.as_str()) {
-                    Some(color) => theme.get_activity_color(color),
-                    None => day.color.clone()
-                };
-                let el = format!(r##"<rect x="{day_start_x}" y="{day_start_y}" width="12" height="12" rx="2" fill="{day_color}" />"##);
-                day_start_y += DAY_BLOCK_SIZE;
-                if day.weekday == 6 {
-                    day_start_x += DAY_BLOCK_SIZE;
-                    day_start_y = block_default_y;
-                }
-
-                el
-            }).collect();
-
-            format!(r##"<g>{0}</g>"##, day_els.join("\n"))
-        }).collect();
+    let mut days: Vec<ActivityDayBlock> = Vec::new();
+
+    for stat in stats.iter() {
+        for day in stat.weeks.iter().flat_map(|week| &week.days) {
+            last_day_x = day_start_x;
+            let day_color = match ActivityColor::from_key(day.color.as_str()) {
+                Some(color) => theme.get_activity_color(color),
+                None => day.color.clone(),
+            };
+            days.push(ActivityDayBlock {
+                x: day_start_x,
+                y: day_start_y,
+                color: day_color,
+            });
+
+            day_start_y += DAY_BLOCK_SIZE;
+            if day.weekday == 6 {
+                day_start_x += DAY_BLOCK_SIZE;
+                day_start_y = block_default_y;
+            }
+        }
 
         let month_el_offset = if month_has_one_week {
             months_start_x + 8
         } else {
             months_start_x
         };
-        let month_title = format!(r##"<text x="{month_el_offset}" y="{month_legend_y}" fill="{0}" class="legend-text">{1}</text>"##, theme_data.text, stat.name);
-        months_legend.push(month_title);
+        months_legend.push(ActivityLegend {
+            x: month_el_offset,
+            y: month_legend_y,
+            name: stat.name.clone(),
+        });
 
         let weeks_count = stat.weeks.len() as i32;
         month_has_one_week = weeks_count == 1;
-        let month_offset = DAY_BLOCK_SIZE * std::cmp::min(weeks_count, 4 );
+        let month_offset = DAY_BLOCK_SIZE * std::cmp::min(weeks_count, 4);
         months_start_x += month_offset;
         if weeks_count >= 5 {
             months_start_x += 5;
         }
-
-        format!(r##"<g>{0}</g>"##, week_els.join("\n"))
-    }).collect();
+    }
 
     let width = (last_day_x + DAY_BLOCK_SIZE * 2) as u32;
-    let week_legend: Vec<String> = ["Mon", "Wed", "Fri"].iter().map(|name| {
-        let el = format!(r##"<text x="20" y="{week_legend_y}" fill="{0}" class="legend-text">{name}</text>"##, theme_data.text);
-        week_legend_y += 32;
-        el
-    }).collect();
+    let week_legend: Vec<ActivityLegend> = ["Mon", "Wed", "Fri"]
+        .iter()
+        .map(|name| {
+            let item = ActivityLegend {
+                x: 20,
+                y: week_legend_y,
+                name: name.to_string(),
+            };
+            week_legend_y += 32;
+            item
+        })
+        .collect();
 
     let template = CompactActivityTemplate {
         name: username,
         theme_data,
-        stats_data: stats_data.join("\n"),
-        months_legend: months_legend.join("\n"),
-        week_legend: week_legend.join("\n"),
+        days,
+        months_legend,
+        week_legend,
         width,
         height,
         with_title,
     };
-    let svg_template = templates::SVGTemplate(template);
+    let svg_template = templates::SVGTemplate(template, max_age);
     templates::SVGTemplate::<CompactActivityTemplate>::into_response(svg_template)
 }
 
 pub async fn get_github_activity_graph(
-    State(cache): State<Cache<String, String>>,
+    State(cache): State<CardCache>,
     Query(params): Query<Params>,
 ) -> Response {
     let username = params.username;
@@ -277,10 +307,35 @@ pub async fn get_github_activity_graph(
         Some(period) => period,
         None => "3_months".to_string(),
     };
-    let with_title = match params.with_title {
-        Some(with_title) => with_title,
-        None => true,
-    };
+    let with_title = params.with_title.unwrap_or(true);
     let activity_res = get_activity_github_intl(cache, &username, &period).await;
+    if params.format == Some(Format::Json) {
+        return templates::JsonData(activity_res).into_response();
+    }
+
     render_activity(username, with_title, theme, activity_res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::svg::parse_svg_texts;
+
+    #[test]
+    fn escapes_user_text() {
+        let template = CompactActivityTemplate {
+            name: "<a>&\"'\u{1}".to_string(),
+            theme_data: Theme::Dark.get_data(),
+            days: vec![],
+            months_legend: vec![],
+            week_legend: vec![],
+            width: 450,
+            height: 150,
+            with_title: true,
+        };
+        let texts = parse_svg_texts(&template.render().unwrap());
+        let title = "<a>&\"''s activity graph";
+
+        assert_eq!(texts.iter().filter(|text| *text == title).count(), 2);
+    }
+}
@@ -1,18 +1,21 @@
 use axum::{response::IntoResponse, Json};
 use serde::Serialize;
 
+use crate::api::github_tokens::{TOKEN_POOL, TokenBudget};
 use crate::data::config::CONFIG;
 
 #[derive(Serialize)]
 pub struct Health {
     version: String,
     status: String,
+    github_tokens: Vec<TokenBudget>,
 }
 
 pub async fn get_health() -> impl IntoResponse {
     let data = Health {
         version: CONFIG.version.clone(),
         status: "ok".to_string(),
+        github_tokens: TOKEN_POOL.get_budgets(),
     };
 
     Json(data)
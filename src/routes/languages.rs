@@ -1,10 +1,16 @@
 use crate::api::github::GraphQLResponse;
-use crate::api::{github, wakatime, wakatime::StatsResponse as WakaTimeStatsResponse};
+use crate::api::wakatime::{
+    Entry as WakaTimeEntry, Stats as WakaTimeStats, StatsRange,
+    StatsResponse as WakaTimeStatsResponse,
+};
+use crate::api::{github, wakatime};
 use crate::data::config::CONFIG;
-use crate::data::language::get_lang_color;
+use crate::data::language::{get_canonical_lang_name, get_lang_color, normalize_lang_name};
 use crate::data::theme::{Theme, ThemeData};
+use crate::data::tool::get_tool_color;
 use crate::prepared_templates::{PreparedTemplate, gh_handle_error_template};
-use crate::templates;
+use crate::templates::{self, Format};
+use crate::utils::cache::{Cached, CardCache};
 
 use askama::Template;
 use axum::{
@@ -12,16 +18,161 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use itertools::Itertools;
-use moka::future::Cache;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 const MAX_BAR_WIDTH: f32 = 275.0;
+const LEGEND_START_Y: u32 = 93;
+const LEGEND_ROW_HEIGHT: u32 = 24;
+const DEFAULT_LANGS_COUNT: usize = 6;
+const MAX_LANGS_COUNT: usize = 20;
+const CONTENT_START_Y: u32 = 62;
+const CHART_CENTER_X: f32 = 235.0;
+const CHART_RADIUS: f32 = 55.0;
+const DONUT_RADIUS: f32 = 45.0;
+const LIST_ROW_HEIGHT: u32 = 36;
+// 100 repositories per page
+const MAX_REPOS_PAGES: usize = 10;
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
+pub enum LanguagesLayout {
+    #[serde(rename = "compact")]
+    Compact,
+    #[serde(rename = "donut")]
+    Donut,
+    #[serde(rename = "pie")]
+    Pie,
+    #[serde(rename = "list")]
+    List,
+}
+
+// weight of Github language by bytes and count of repos with it
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
+pub enum LanguagesWeight {
+    #[serde(rename = "bytes")]
+    Bytes,
+    #[serde(rename = "repos")]
+    Repos,
+    #[serde(rename = "hybrid")]
+    Hybrid,
+}
+
+impl LanguagesWeight {
+    // exponents of bytes and repos count
+    pub fn get_exponents(&self) -> (f64, f64) {
+        match self {
+            LanguagesWeight::Bytes => (1.0, 0.0),
+            LanguagesWeight::Repos => (0.0, 1.0),
+            LanguagesWeight::Hybrid => (0.5, 0.5),
+        }
+    }
+}
 
 #[derive(Deserialize, Serialize)]
 pub struct Params {
     username: String,
     theme: Option<Theme>,
+    weight: Option<LanguagesWeight>,
+    // comma separated repo names, e.g. exclude_repo=dotfiles,vendored-lib
+    exclude_repo: Option<String>,
+    include_forks: Option<bool>,
+    langs_count: Option<usize>,
+    layout: Option<LanguagesLayout>,
+    // comma separated languages, e.g. exclude=HTML,Makefile and merge=Vue:TypeScript
+    exclude: Option<String>,
+    only: Option<String>,
+    merge: Option<String>,
+    format: Option<Format>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WakaParams {
+    username: String,
+    range: Option<StatsRange>,
+    theme: Option<Theme>,
+    langs_count: Option<usize>,
+    layout: Option<LanguagesLayout>,
+    exclude: Option<String>,
+    only: Option<String>,
+    merge: Option<String>,
+    format: Option<Format>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WakapiParams {
+    username: String,
+    // one of WAKAPI_HOSTS, first by default
+    host: Option<String>,
+    range: Option<StatsRange>,
+    theme: Option<Theme>,
+    langs_count: Option<usize>,
+    layout: Option<LanguagesLayout>,
+    exclude: Option<String>,
+    only: Option<String>,
+    merge: Option<String>,
+    format: Option<Format>,
+}
+
+// kind of entries rendered with languages layout, Github has languages only
+#[derive(Debug, Clone, Copy)]
+pub enum StatsKind {
+    Languages,
+    Editors,
+    OperatingSystems,
+    Categories,
+}
+
+impl StatsKind {
+    pub fn get_key(&self) -> &'static str {
+        match self {
+            StatsKind::Languages => "langs",
+            StatsKind::Editors => "editors",
+            StatsKind::OperatingSystems => "os",
+            StatsKind::Categories => "categories",
+        }
+    }
+
+    pub fn get_title(&self) -> &'static str {
+        match self {
+            StatsKind::Languages => "Most Used Languages",
+            StatsKind::Editors => "Most Used Editors",
+            StatsKind::OperatingSystems => "Most Used OS",
+            StatsKind::Categories => "Top Categories",
+        }
+    }
+
+    fn get_color(&self, name: &str) -> String {
+        match self {
+            StatsKind::Languages => get_lang_color(name),
+            _ => get_tool_color(name),
+        }
+    }
+
+    fn get_not_found(&self) -> PreparedTemplate {
+        match self {
+            StatsKind::Languages => PreparedTemplate::FailedFindLanguages,
+            _ => PreparedTemplate::FailedFindStats,
+        }
+    }
+
+    fn get_entries(&self, stats: WakaTimeStats) -> Vec<WakaTimeEntry> {
+        match self {
+            StatsKind::Languages => stats.languages,
+            StatsKind::Editors => stats.editors.unwrap_or_default(),
+            StatsKind::OperatingSystems => stats.operating_systems.unwrap_or_default(),
+            StatsKind::Categories => stats.categories.unwrap_or_default(),
+        }
+    }
+}
+
+// languages of one Github repo, cached to apply weight and repo filters per request
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RepoLanguages {
+    name: String,
+    is_fork: bool,
+    // language name and size in bytes
+    languages: Vec<(String, i64)>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -31,33 +182,172 @@ pub struct LanguageStat {
     percent: f32,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct MinimalLanguageStat {
+// exclude, only and merge params, languages are matched by normalized names
+pub struct LangsFilter {
+    exclude: HashSet<String>,
+    only: HashSet<String>,
+    // normalized source -> target name
+    merge: HashMap<String, String>,
+    // kind of stats, merge targets missing in stats get its colors
+    kind: StatsKind,
+}
+
+fn split_param(param: &Option<String>) -> impl Iterator<Item = &str> {
+    param
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+}
+
+impl LangsFilter {
+    pub fn new(
+        kind: StatsKind,
+        exclude: &Option<String>,
+        only: &Option<String>,
+        merge: &Option<String>,
+    ) -> Self {
+        Self {
+            kind,
+            exclude: split_param(exclude).map(normalize_lang_name).collect(),
+            only: split_param(only).map(normalize_lang_name).collect(),
+            merge: split_param(merge)
+                .filter_map(|pair| pair.split_once(':'))
+                .map(|(source, target)| (normalize_lang_name(source), target.trim().to_string()))
+                .filter(|(_, target)| !target.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn apply(&self, stats: Vec<LanguageStat>) -> Vec<LanguageStat> {
+        let mut merged_stats: Vec<(String, LanguageStat)> = Vec::new();
+        for stat in stats {
+            let stat_key = normalize_lang_name(&stat.name);
+            let target = self.merge.get(&stat_key);
+            let key = target.map_or(stat_key, |target| normalize_lang_name(target));
+
+            match merged_stats
+                .iter_mut()
+                .find(|(merged_key, _)| *merged_key == key)
+            {
+                Some((_, merged_stat)) => {
+                    merged_stat.percent += stat.percent;
+                    // name and color of target language are preferred over merged ones
+                    if target.is_none() {
+                        merged_stat.name = stat.name;
+                        merged_stat.color = stat.color;
+                    }
+                }
+                None => {
+                    let (name, color) = match target {
+                        Some(target) => (
+                            get_canonical_lang_name(target)
+                                .map_or(target.clone(), |name| name.to_string()),
+                            self.kind.get_color(target),
+                        ),
+                        None => (stat.name, stat.color),
+                    };
+                    merged_stats.push((
+                        key,
+                        LanguageStat {
+                            name,
+                            color,
+                            percent: stat.percent,
+                        },
+                    ))
+                }
+            }
+        }
+
+        merged_stats
+            .into_iter()
+            .filter(|(key, _)| !self.exclude.contains(key))
+            .filter(|(key, _)| self.only.is_empty() || self.only.contains(key))
+            .map(|(_, stat)| stat)
+            .sorted_by(|a, b| b.percent.total_cmp(&a.percent))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct LanguageBar {
+    x: f32,
+    width: f32,
+    color: String,
+}
+
+#[derive(Debug)]
+pub struct LanguageLegend {
+    x: u32,
+    y: u32,
     name: String,
-    count: f32,
+    color: String,
+    percent: f32,
 }
 
 #[derive(Template)]
-#[template(path = "compact/languages.html")]
+#[template(path = "compact/languages.html", escape = "svg")]
 pub struct CompactLanguagesTemplate {
     name: String,
-    stats_bar: String,
-    bar_legend: String,
+    height: u32,
+    title: &'static str,
+    // stats range of Wakatime cards
+    range: Option<&'static str>,
+    bars: Vec<LanguageBar>,
+    legend: Vec<LanguageLegend>,
     theme_data: ThemeData,
 }
 
-async fn get_top_langs_by_waka_intl(
-    cache: Cache<String, String>,
-    username: &String,
-) -> Result<Vec<LanguageStat>, PreparedTemplate> {
-    let cache_key = format!("wakatime:langs:{username}");
-    if let Some(cached) = cache.get(&cache_key).await {
-        let langs = serde_json::from_str(&cached).unwrap();
-        return Ok(langs);
-    }
+// slice of donut or pie chart
+#[derive(Debug)]
+pub struct LanguageSlice {
+    path: String,
+    color: String,
+}
+
+#[derive(Template)]
+#[template(path = "chart/languages.html", escape = "svg")]
+pub struct ChartLanguagesTemplate {
+    name: String,
+    height: u32,
+    title: &'static str,
+    range: Option<&'static str>,
+    is_donut: bool,
+    slices: Vec<LanguageSlice>,
+    legend: Vec<LanguageLegend>,
+    theme_data: ThemeData,
+}
+
+#[derive(Debug)]
+pub struct LanguageRow {
+    y: u32,
+    name: String,
+    color: String,
+    percent: f32,
+    width: f32,
+}
+
+#[derive(Template)]
+#[template(path = "list/languages.html", escape = "svg")]
+pub struct ListLanguagesTemplate {
+    name: String,
+    height: u32,
+    title: &'static str,
+    range: Option<&'static str>,
+    rows: Vec<LanguageRow>,
+    theme_data: ThemeData,
+}
 
-    let stats = wakatime::get_stats(username).await;
-    if !stats.is_ok() {
+async fn waka_top_stats_get_data(
+    api_url: String,
+    username: String,
+    range: StatsRange,
+    kind: StatsKind,
+    api_key: Option<String>,
+) -> Result<Vec<LanguageStat>, PreparedTemplate> {
+    let stats = wakatime::get_stats(&api_url, &username, range, api_key.as_ref()).await;
+    if stats.is_err() {
         return Err(PreparedTemplate::Unknown);
     }
 
@@ -65,196 +355,676 @@ async fn get_top_langs_by_waka_intl(
         WakaTimeStatsResponse::Failed(err) => {
             let err_template = match err.error.as_str() {
                 "Not found." => PreparedTemplate::FailedFindUser,
-                "Time range not matching user's public stats range." => {
-                    PreparedTemplate::FailedFindLanguages
-                }
+                "Time range not matching user's public stats range." => kind.get_not_found(),
                 _ => PreparedTemplate::Unknown,
             };
             return Err(err_template);
         }
         WakaTimeStatsResponse::NoData(_) => {
-            return Err(PreparedTemplate::FailedFindLanguages);
+            return Err(kind.get_not_found());
         }
         WakaTimeStatsResponse::Valid(res) => res,
     };
 
-    let languages = stats_data.data.languages;
-    let first_languages = match languages.first_chunk::<6>() {
-        Some(langs) => langs,
-        None => {
-            return Err(PreparedTemplate::FailedFindLanguages);
-        }
-    };
+    let entries = kind.get_entries(stats_data.data);
+    let first_entries: Vec<&WakaTimeEntry> =
+        entries.iter().filter(|entry| entry.percent > 0.0).collect();
+    if first_entries.is_empty() {
+        return Err(kind.get_not_found());
+    }
 
-    let max_percent = first_languages
+    let top_stats: Vec<LanguageStat> = first_entries
         .iter()
-        .fold(0.0, |acc, val| acc + val.percent);
-    let top_langs: Vec<LanguageStat> = first_languages
-        .iter()
-        .map(|lang| LanguageStat {
-            name: lang.name.clone(),
-            color: get_lang_color(&lang.name),
-            percent: 100.0 / (max_percent / lang.percent),
+        .map(|entry| LanguageStat {
+            name: entry.name.clone(),
+            color: kind.get_color(&entry.name),
+            percent: entry.percent,
         })
         .collect();
 
-    let cache_body = serde_json::to_string(&top_langs).unwrap();
-    cache.insert(cache_key, cache_body).await;
-
-    Ok(top_langs)
+    Ok(top_stats)
 }
 
-async fn get_top_langs_by_github_intl(
-    cache: Cache<String, String>,
+async fn get_top_stats_by_waka_intl(
+    cache: CardCache,
     username: &String,
-) -> Result<Vec<LanguageStat>, PreparedTemplate> {
-    let cache_key = format!("github:langs:{username}");
-    if let Some(cached) = cache.get(&cache_key).await {
-        let langs = serde_json::from_str(&cached).unwrap();
-        return Ok(langs);
+    range: StatsRange,
+    kind: StatsKind,
+) -> Result<Cached<Vec<LanguageStat>>, PreparedTemplate> {
+    if !wakatime::is_valid_username(username) {
+        return Err(PreparedTemplate::FailedFindUser);
     }
 
-    let stats = github::get_languages(username).await;
-    if !stats.is_ok() {
-        return Err(PreparedTemplate::Unknown);
-    }
+    let cache_key = format!("wakatime:{}:{username}:{}", kind.get_key(), range.as_str());
+    cache
+        .get_or_fetch(
+            cache_key,
+            waka_top_stats_get_data(
+                CONFIG.wakatime_api_url.clone(),
+                username.clone(),
+                range,
+                kind,
+                wakatime::get_api_key(username),
+            ),
+        )
+        .await
+}
 
-    let languages_raw_data = match stats.unwrap() {
-        GraphQLResponse::Failed(err) => return Err(gh_handle_error_template(err)),
-        GraphQLResponse::Valid(res) => match res.data.user {
-            None => return Err(PreparedTemplate::FailedFindUser),
-            Some(user_data) => user_data.repositories.nodes,
-        },
+async fn get_top_langs_by_wakapi_intl(
+    cache: CardCache,
+    host: &str,
+    username: &String,
+    range: StatsRange,
+) -> Result<Cached<Vec<LanguageStat>>, PreparedTemplate> {
+    let Some(api_url) = wakatime::get_wakapi_api_url(host) else {
+        return Err(PreparedTemplate::HostNotAllowed);
     };
 
-    if languages_raw_data.len() == 0 {
+    if !wakatime::is_valid_username(username) {
+        return Err(PreparedTemplate::FailedFindUser);
+    }
+
+    let cache_key = format!("wakapi:langs:{api_url}:{username}:{}", range.as_str());
+    cache
+        .get_or_fetch(
+            cache_key,
+            // api keys are for wakatime.com only and aren't sent to Wakapi
+            waka_top_stats_get_data(api_url, username.clone(), range, StatsKind::Languages, None),
+        )
+        .await
+}
+
+async fn github_repos_langs_get_data(
+    username: String,
+) -> Result<Vec<RepoLanguages>, PreparedTemplate> {
+    let mut repos: Vec<RepoLanguages> = Vec::new();
+    let mut after: Option<String> = None;
+    for _ in 0..MAX_REPOS_PAGES {
+        let stats = github::get_languages(&username, after.as_ref()).await;
+        if stats.is_err() {
+            return Err(PreparedTemplate::Unknown);
+        }
+
+        let repositories = match stats.unwrap() {
+            GraphQLResponse::Failed(err) => return Err(gh_handle_error_template(err)),
+            GraphQLResponse::Valid(res) => match res.data.user {
+                None => return Err(PreparedTemplate::FailedFindUser),
+                Some(user_data) => user_data.repositories,
+            },
+        };
+
+        repos.extend(repositories.nodes.into_iter().map(|repo| {
+            RepoLanguages {
+                name: repo.name,
+                is_fork: repo.is_fork,
+                languages: repo
+                    .languages
+                    .edges
+                    .into_iter()
+                    .map(|lang| (lang.node.name, lang.size as i64))
+                    .collect(),
+            }
+        }));
+
+        if !repositories.page_info.has_next_page {
+            break;
+        }
+
+        after = repositories.page_info.end_cursor;
+    }
+
+    if repos.is_empty() {
         return Err(PreparedTemplate::FailedFindLanguages);
     }
 
-    let mut langs_data: HashMap<String, i64> = HashMap::new();
-    languages_raw_data
-        .iter()
-        .filter(|&lang| lang.languages.edges.len() > 0)
-        .flat_map(|lang| &lang.languages.edges)
-        .for_each(|lang| {
-            langs_data
-                .entry(lang.node.name.clone())
-                .and_modify(|count| {
-                    *count += lang.size as i64;
-                })
-                .or_insert(lang.size as i64);
-        });
+    Ok(repos)
+}
 
-    let first_languages = langs_data
+// language score is bytes^a * repos_count^b summed over repos left after filters
+fn calc_github_langs(
+    repos: Vec<RepoLanguages>,
+    weight: LanguagesWeight,
+    exclude_repos: &HashSet<String>,
+    include_forks: bool,
+) -> Result<Vec<LanguageStat>, PreparedTemplate> {
+    // language -> (bytes, repos count)
+    let mut langs_data: HashMap<String, (i64, u32)> = HashMap::new();
+    repos
         .iter()
-        .sorted_by(|a, b| Ord::cmp(&b.1, &a.1))
-        .take(6)
-        .collect::<Vec<(&String, &i64)>>();
+        .filter(|repo| include_forks || !repo.is_fork)
+        .filter(|repo| !exclude_repos.contains(&repo.name.to_lowercase()))
+        .flat_map(|repo| &repo.languages)
+        .filter(|(_, size)| *size > 0)
+        .for_each(|(name, size)| {
+            let lang_data = langs_data.entry(name.clone()).or_insert((0, 0));
+            lang_data.0 += size;
+            lang_data.1 += 1;
+        });
 
-    let max_bytes = first_languages
-        .iter()
-        .fold(0 as i64, |acc, val| acc + val.1) as f64;
-    let top_langs: Vec<LanguageStat> = first_languages
-        .iter()
-        .map(|(name, size)| {
-            let lang_name = name.to_string();
-            let lang_size = **size as f64;
-            let percent = (lang_size / max_bytes * 100.0) as f32;
-
-            LanguageStat {
-                name: lang_name,
-                color: get_lang_color(name),
-                percent,
-            }
+    let (bytes_exp, repos_exp) = weight.get_exponents();
+    let langs_scores: Vec<(String, f64)> = langs_data
+        .into_iter()
+        .map(|(name, (bytes, repos_count))| {
+            let score = (bytes as f64).powf(bytes_exp) * (repos_count as f64).powf(repos_exp);
+            (name, score)
         })
+        .sorted_by(|a, b| b.1.total_cmp(&a.1))
         .collect();
+    if langs_scores.is_empty() {
+        return Err(PreparedTemplate::FailedFindLanguages);
+    }
 
-    let cache_body = serde_json::to_string(&top_langs).unwrap();
-    cache.insert(cache_key, cache_body).await;
+    let max_score = langs_scores.iter().map(|(_, score)| score).sum::<f64>();
+    let top_langs: Vec<LanguageStat> = langs_scores
+        .into_iter()
+        .map(|(name, score)| LanguageStat {
+            color: get_lang_color(&name),
+            name,
+            percent: (score / max_score * 100.0) as f32,
+        })
+        .collect();
 
     Ok(top_langs)
 }
 
-pub fn render_top_langs(
-    username: String,
-    theme: Theme,
-    top_langs_res: Result<Vec<LanguageStat>, PreparedTemplate>,
-) -> Response {
-    if !top_langs_res.is_ok() {
-        return top_langs_res.unwrap_err().render();
+async fn get_top_langs_by_github_intl(
+    cache: CardCache,
+    username: &String,
+    weight: LanguagesWeight,
+    exclude_repos: &HashSet<String>,
+    include_forks: bool,
+) -> Result<Cached<Vec<LanguageStat>>, PreparedTemplate> {
+    if !github::is_valid_login(username) {
+        return Err(PreparedTemplate::FailedFindUser);
     }
 
-    let theme_data = theme.get_data();
-    let stats = top_langs_res.unwrap();
+    // all repos are cached, so weight and filters don't need own requests
+    let cache_key = format!("github:langs:{username}");
+    cache
+        .get_or_fetch(cache_key, github_repos_langs_get_data(username.clone()))
+        .await?
+        .try_map(|repos| calc_github_langs(repos, weight, exclude_repos, include_forks))
+}
+
+fn get_langs_count(langs_count: Option<usize>) -> usize {
+    langs_count
+        .unwrap_or(DEFAULT_LANGS_COUNT)
+        .clamp(1, MAX_LANGS_COUNT)
+}
+
+// filtered first count of stats, error if all stats are filtered out
+fn get_shown_stats(
+    stats: Vec<LanguageStat>,
+    langs_filter: &LangsFilter,
+    langs_count: usize,
+    not_found: PreparedTemplate,
+) -> Result<Vec<LanguageStat>, PreparedTemplate> {
+    let stats = langs_filter.apply(stats);
+    if stats.is_empty() {
+        return Err(not_found);
+    }
+
+    Ok(get_first_stats(stats, langs_count))
+}
+
+// first count of stats with percents recalculated to 100% in total
+fn get_first_stats(stats: Vec<LanguageStat>, count: usize) -> Vec<LanguageStat> {
+    let first_stats: Vec<LanguageStat> = stats.into_iter().take(count).collect();
+    let max_percent = first_stats.iter().fold(0.0, |acc, val| acc + val.percent);
+    first_stats
+        .into_iter()
+        .map(|stat| LanguageStat {
+            percent: 100.0 / (max_percent / stat.percent),
+            ..stat
+        })
+        .collect()
+}
+
+fn get_arc_point(cx: f32, cy: f32, radius: f32, angle: f32) -> (f32, f32) {
+    (cx + radius * angle.cos(), cy + radius * angle.sin())
+}
+
+// svg path of circle arc clockwise from start to end angle in radians, pie slice if is_sector.
+// full circle is drawn with two arcs because arc with same start and end point isn't rendered
+fn get_arc_path(cx: f32, cy: f32, radius: f32, start: f32, end: f32, is_sector: bool) -> String {
+    let sweep = end - start;
+    if sweep >= std::f32::consts::TAU - 0.001 {
+        let (x1, y1) = get_arc_point(cx, cy, radius, start);
+        let (x2, y2) = get_arc_point(cx, cy, radius, start + std::f32::consts::PI);
+        return format!(
+            "M {x1:.2} {y1:.2} A {radius} {radius} 0 1 1 {x2:.2} {y2:.2} A {radius} {radius} 0 1 1 {x1:.2} {y1:.2} Z"
+        );
+    }
+
+    let (x1, y1) = get_arc_point(cx, cy, radius, start);
+    let (x2, y2) = get_arc_point(cx, cy, radius, end);
+    let large_arc = if sweep > std::f32::consts::PI { 1 } else { 0 };
+    let arc = format!("A {radius} {radius} 0 {large_arc} 1 {x2:.2} {y2:.2}");
+    if is_sector {
+        format!("M {cx} {cy} L {x1:.2} {y1:.2} {arc} Z")
+    } else {
+        format!("M {x1:.2} {y1:.2} {arc}")
+    }
+}
+
+fn render_compact_langs(
+    username: String,
+    title: &'static str,
+    range: Option<&'static str>,
+    theme_data: ThemeData,
+    stats: Vec<LanguageStat>,
+    max_age: Duration,
+) -> Response {
     let mut bar_start_x = 20.0;
-    let mut column_start_y = 93;
+    // two columns, first one is filled first
+    let rows_count = stats.len().div_ceil(2) as u32;
+    let height = LEGEND_START_Y + rows_count * LEGEND_ROW_HEIGHT + 10;
 
-    let bar_data: Vec<String> = stats
+    let bars: Vec<LanguageBar> = stats
         .iter()
         .map(|stat| {
             let stat_percent = stat.percent / 100.0;
             let block_width = MAX_BAR_WIDTH * stat_percent;
-            let element = format!(
-                r##"<rect mask="url(#stats_mask)" x="{bar_start_x:.2}" y="61" width="{block_width:.2}" height="10" fill="{0}" />"##,
-                stat.color
-            );
+            let bar = LanguageBar {
+                x: bar_start_x,
+                width: block_width,
+                color: stat.color.clone(),
+            };
 
             bar_start_x += block_width;
 
-            element
+            bar
         })
         .collect();
 
-    let bar_legend: Vec<String> = stats
+    let legend: Vec<LanguageLegend> = stats
         .iter()
         .enumerate()
         .map(|(idx, stat)| {
-            let start_x = if idx < 3 { 20 } else { 175 };
-            let text_x = start_x + 18;
-            let start_y = column_start_y;
-            let text_y = start_y + 11;
-            let element = format!(
-                r##"
-                <g>
-                    <rect x="{start_x}" y="{start_y}" width="12" height="12" rx="6" fill="{0}" />
-                    <text x="{text_x}" y="{text_y}" fill="{1}" class="stat-text">{2} {3:.2}%</text>
-                </g>
-            "##,
-                stat.color, &theme_data.text, stat.name, stat.percent,
-            );
-
-            column_start_y = if idx == 2 { 93 } else { column_start_y + 24 };
-
-            element
+            let idx = idx as u32;
+            LanguageLegend {
+                x: if idx < rows_count { 20 } else { 175 },
+                y: LEGEND_START_Y + (idx % rows_count) * LEGEND_ROW_HEIGHT,
+                name: stat.name.clone(),
+                color: stat.color.clone(),
+                percent: stat.percent,
+            }
         })
         .collect();
 
     let template = CompactLanguagesTemplate {
         name: username,
-        stats_bar: bar_data.join("\n"),
-        bar_legend: bar_legend.join("\n"),
+        height,
+        title,
+        range,
+        bars,
+        legend,
         theme_data,
     };
-    let svg_template = templates::SVGTemplate(template);
+    let svg_template = templates::SVGTemplate(template, max_age);
     templates::SVGTemplate::<CompactLanguagesTemplate>::into_response(svg_template)
 }
 
+// legend on the left and chart on the right
+fn render_chart_langs(
+    username: String,
+    title: &'static str,
+    range: Option<&'static str>,
+    is_donut: bool,
+    theme_data: ThemeData,
+    stats: Vec<LanguageStat>,
+    max_age: Duration,
+) -> Response {
+    let legend_height = stats.len() as u32 * LEGEND_ROW_HEIGHT;
+    let content_height = legend_height.max((CHART_RADIUS * 2.0) as u32);
+    let height = CONTENT_START_Y + content_height + 15;
+    let center_y = (CONTENT_START_Y + content_height / 2) as f32;
+    let radius = if is_donut { DONUT_RADIUS } else { CHART_RADIUS };
+
+    // slices start from the top
+    let mut start_angle = -std::f32::consts::FRAC_PI_2;
+    let slices: Vec<LanguageSlice> = stats
+        .iter()
+        .map(|stat| {
+            let end_angle = start_angle + std::f32::consts::TAU * stat.percent / 100.0;
+            let slice = LanguageSlice {
+                path: get_arc_path(
+                    CHART_CENTER_X,
+                    center_y,
+                    radius,
+                    start_angle,
+                    end_angle,
+                    !is_donut,
+                ),
+                color: stat.color.clone(),
+            };
+
+            start_angle = end_angle;
+
+            slice
+        })
+        .collect();
+
+    // legend is centered vertically with chart
+    let legend_start_y = CONTENT_START_Y + (content_height - legend_height) / 2;
+    let legend: Vec<LanguageLegend> = stats
+        .iter()
+        .enumerate()
+        .map(|(idx, stat)| LanguageLegend {
+            x: 20,
+            y: legend_start_y + idx as u32 * LEGEND_ROW_HEIGHT,
+            name: stat.name.clone(),
+            color: stat.color.clone(),
+            percent: stat.percent,
+        })
+        .collect();
+
+    let template = ChartLanguagesTemplate {
+        name: username,
+        height,
+        title,
+        range,
+        is_donut,
+        slices,
+        legend,
+        theme_data,
+    };
+    let svg_template = templates::SVGTemplate(template, max_age);
+    templates::SVGTemplate::<ChartLanguagesTemplate>::into_response(svg_template)
+}
+
+// row with name, percent and own progress bar for every language
+fn render_list_langs(
+    username: String,
+    title: &'static str,
+    range: Option<&'static str>,
+    theme_data: ThemeData,
+    stats: Vec<LanguageStat>,
+    max_age: Duration,
+) -> Response {
+    let height = CONTENT_START_Y + stats.len() as u32 * LIST_ROW_HEIGHT + 10;
+    let rows: Vec<LanguageRow> = stats
+        .iter()
+        .enumerate()
+        .map(|(idx, stat)| LanguageRow {
+            y: CONTENT_START_Y + idx as u32 * LIST_ROW_HEIGHT,
+            name: stat.name.clone(),
+            color: stat.color.clone(),
+            percent: stat.percent,
+            width: MAX_BAR_WIDTH * stat.percent / 100.0,
+        })
+        .collect();
+
+    let template = ListLanguagesTemplate {
+        name: username,
+        height,
+        title,
+        range,
+        rows,
+        theme_data,
+    };
+    let svg_template = templates::SVGTemplate(template, max_age);
+    templates::SVGTemplate::<ListLanguagesTemplate>::into_response(svg_template)
+}
+
+pub fn render_top_langs(
+    username: String,
+    title: &'static str,
+    range: Option<StatsRange>,
+    layout: LanguagesLayout,
+    theme: Theme,
+    top_langs_res: Result<Cached<Vec<LanguageStat>>, PreparedTemplate>,
+) -> Response {
+    let Cached {
+        data: stats,
+        max_age,
+    } = match top_langs_res {
+        Ok(stats) => stats,
+        Err(err) => return err.render(),
+    };
+
+    let theme_data = theme.get_data();
+    let range = range.map(|range| range.get_title());
+    match layout {
+        LanguagesLayout::Compact => {
+            render_compact_langs(username, title, range, theme_data, stats, max_age)
+        }
+        LanguagesLayout::Donut => {
+            render_chart_langs(username, title, range, true, theme_data, stats, max_age)
+        }
+        LanguagesLayout::Pie => {
+            render_chart_langs(username, title, range, false, theme_data, stats, max_age)
+        }
+        LanguagesLayout::List => {
+            render_list_langs(username, title, range, theme_data, stats, max_age)
+        }
+    }
+}
+
+async fn get_waka_top_stats(cache: CardCache, params: WakaParams, kind: StatsKind) -> Response {
+    let username = params.username;
+    let theme = params.theme.unwrap_or(CONFIG.default_theme.clone());
+    let range = params.range.unwrap_or(StatsRange::AllTime);
+    let langs_count = get_langs_count(params.langs_count);
+    let langs_filter = LangsFilter::new(kind, &params.exclude, &params.only, &params.merge);
+    let top_stats_res = get_top_stats_by_waka_intl(cache, &username, range, kind)
+        .await
+        .and_then(|stats| {
+            stats.try_map(|stats| {
+                get_shown_stats(stats, &langs_filter, langs_count, kind.get_not_found())
+            })
+        });
+    if params.format == Some(Format::Json) {
+        return templates::JsonData(top_stats_res).into_response();
+    }
+
+    render_top_langs(
+        username,
+        kind.get_title(),
+        Some(range),
+        params.layout.unwrap_or(LanguagesLayout::Compact),
+        theme,
+        top_stats_res,
+    )
+}
+
 pub async fn get_waka_top_langs(
-    State(cache): State<Cache<String, String>>,
-    Query(params): Query<Params>,
+    State(cache): State<CardCache>,
+    Query(params): Query<WakaParams>,
+) -> Response {
+    get_waka_top_stats(cache, params, StatsKind::Languages).await
+}
+
+pub async fn get_waka_top_editors(
+    State(cache): State<CardCache>,
+    Query(params): Query<WakaParams>,
+) -> Response {
+    get_waka_top_stats(cache, params, StatsKind::Editors).await
+}
+
+pub async fn get_waka_top_os(
+    State(cache): State<CardCache>,
+    Query(params): Query<WakaParams>,
+) -> Response {
+    get_waka_top_stats(cache, params, StatsKind::OperatingSystems).await
+}
+
+pub async fn get_waka_top_categories(
+    State(cache): State<CardCache>,
+    Query(params): Query<WakaParams>,
+) -> Response {
+    get_waka_top_stats(cache, params, StatsKind::Categories).await
+}
+
+pub async fn get_wakapi_top_langs(
+    State(cache): State<CardCache>,
+    Query(params): Query<WakapiParams>,
 ) -> Response {
     let username = params.username;
     let theme = params.theme.unwrap_or(CONFIG.default_theme.clone());
-    let top_langs_res = get_top_langs_by_waka_intl(cache, &username).await;
-    render_top_langs(username, theme, top_langs_res)
+    let host = params
+        .host
+        .or(CONFIG.wakapi_hosts.first().cloned())
+        .unwrap_or_default();
+    let range = params.range.unwrap_or(StatsRange::AllTime);
+    let langs_count = get_langs_count(params.langs_count);
+    let langs_filter = LangsFilter::new(
+        StatsKind::Languages,
+        &params.exclude,
+        &params.only,
+        &params.merge,
+    );
+    let top_langs_res = get_top_langs_by_wakapi_intl(cache, &host, &username, range)
+        .await
+        .and_then(|stats| {
+            stats.try_map(|stats| {
+                get_shown_stats(
+                    stats,
+                    &langs_filter,
+                    langs_count,
+                    PreparedTemplate::FailedFindLanguages,
+                )
+            })
+        });
+    if params.format == Some(Format::Json) {
+        return templates::JsonData(top_langs_res).into_response();
+    }
+
+    render_top_langs(
+        username,
+        StatsKind::Languages.get_title(),
+        Some(range),
+        params.layout.unwrap_or(LanguagesLayout::Compact),
+        theme,
+        top_langs_res,
+    )
 }
 
 pub async fn get_github_top_langs(
-    State(cache): State<Cache<String, String>>,
+    State(cache): State<CardCache>,
     Query(params): Query<Params>,
 ) -> Response {
     let username = params.username;
     let theme = params.theme.unwrap_or(CONFIG.default_theme.clone());
-    let top_langs_res = get_top_langs_by_github_intl(cache, &username).await;
-    render_top_langs(username, theme, top_langs_res)
+    let langs_count = get_langs_count(params.langs_count);
+    let langs_filter = LangsFilter::new(
+        StatsKind::Languages,
+        &params.exclude,
+        &params.only,
+        &params.merge,
+    );
+    let weight = params.weight.unwrap_or(LanguagesWeight::Bytes);
+    let exclude_repos: HashSet<String> = split_param(&params.exclude_repo)
+        .map(|repo| repo.to_lowercase())
+        .collect();
+    let include_forks = params.include_forks.unwrap_or(false);
+    let top_langs_res =
+        get_top_langs_by_github_intl(cache, &username, weight, &exclude_repos, include_forks)
+            .await
+            .and_then(|stats| {
+                stats.try_map(|stats| {
+                    get_shown_stats(
+                        stats,
+                        &langs_filter,
+                        langs_count,
+                        PreparedTemplate::FailedFindLanguages,
+                    )
+                })
+            });
+    if params.format == Some(Format::Json) {
+        return templates::JsonData(top_langs_res).into_response();
+    }
+
+    render_top_langs(
+        username,
+        StatsKind::Languages.get_title(),
+        None,
+        params.layout.unwrap_or(LanguagesLayout::Compact),
+        theme,
+        top_langs_res,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::svg::parse_svg_texts;
+
+    #[test]
+    fn escapes_user_text() {
+        let template = CompactLanguagesTemplate {
+            name: "<a>&\"'\u{1}".to_string(),
+            height: 165,
+            title: "Most Used Languages",
+            range: Some("Last 7 Days"),
+            bars: vec![],
+            legend: vec![LanguageLegend {
+                x: 20,
+                y: 93,
+                name: "<lang>&".to_string(),
+                color: "#fff".to_string(),
+                percent: 100.0,
+            }],
+            theme_data: Theme::Dark.get_data(),
+        };
+        let texts = parse_svg_texts(&template.render().unwrap());
+
+        assert!(texts.contains(&"<a>&\"''s most used languages".to_string()));
+        assert!(texts.contains(&"<lang>& 100.00%".to_string()));
+    }
+
+    fn create_test_stat(name: &str, percent: f32) -> LanguageStat {
+        LanguageStat {
+            name: name.to_string(),
+            color: "#000000".to_string(),
+            percent,
+        }
+    }
+
+    #[test]
+    fn merges_into_missing_target() {
+        let merge = Some("Vue:ts,Svelte:Frontend".to_string());
+        let filter = LangsFilter::new(StatsKind::Languages, &None, &None, &merge);
+        let stats = filter.apply(vec![
+            create_test_stat("Vue", 60.0),
+            create_test_stat("Svelte", 30.0),
+            create_test_stat("Rust", 10.0),
+        ]);
+
+        assert_eq!(stats[0].name, "TypeScript");
+        assert_eq!(stats[0].color, get_lang_color("TypeScript"));
+        assert_eq!(stats[1].name, "Frontend");
+        assert_eq!(stats[1].color, get_lang_color("Frontend"));
+        assert_eq!(stats[2].name, "Rust");
+    }
+
+    #[test]
+    fn merges_into_present_target() {
+        let merge = Some("Vue:typescript".to_string());
+        let filter = LangsFilter::new(StatsKind::Languages, &None, &None, &merge);
+        let stats = filter.apply(vec![
+            create_test_stat("Vue", 60.0),
+            create_test_stat("TypeScript", 30.0),
+        ]);
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].name, "TypeScript");
+        assert_eq!(stats[0].color, "#000000");
+        assert_eq!(stats[0].percent, 90.0);
+    }
+
+    #[test]
+    fn merges_into_canonical_name_of_target() {
+        let merge = Some("SCSS:css,Vue:html".to_string());
+        let filter = LangsFilter::new(StatsKind::Languages, &None, &None, &merge);
+        let stats = filter.apply(vec![
+            create_test_stat("SCSS", 60.0),
+            create_test_stat("Vue", 40.0),
+        ]);
+
+        assert_eq!(stats[0].name, "CSS");
+        assert_eq!(stats[0].color, get_lang_color("CSS"));
+        assert_eq!(stats[1].name, "HTML");
+        assert_eq!(stats[1].color, get_lang_color("HTML"));
+    }
 }
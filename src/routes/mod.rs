@@ -3,3 +3,5 @@ pub mod health;
 pub mod index;
 pub mod languages;
 pub mod pin;
+pub mod stats;
+pub mod streak;
@@ -8,9 +8,11 @@ use crate::data::config::CONFIG;
 use crate::data::language::get_lang_color;
 use crate::data::theme::{Theme, ThemeData};
 use crate::prepared_templates::{
-    PreparedTemplate, gh_handle_error_template, hf_handle_error_template,
+    PreparedTemplate, gh_handle_error_template, gh_repo_handle_error_template,
+    hf_handle_error_template,
 };
-use crate::templates;
+use crate::templates::{self, Format};
+use crate::utils::cache::{Cached, CardCache};
 use crate::utils::svg::wrap_text;
 use crate::utils::utils::fmt_num;
 use crate::{
@@ -26,7 +28,6 @@ use axum::{
     extract::{Query, State},
     response::{IntoResponse, Response},
 };
-use moka::future::Cache;
 use serde::{Deserialize, Serialize};
 
 const MAX_PIN_WIDTH: usize = 400;
@@ -39,6 +40,7 @@ pub struct HFParams {
     #[serde(rename = "type")]
     typename: HFPinIcon,
     show_owner: Option<bool>,
+    format: Option<Format>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -47,6 +49,7 @@ pub struct GHParams {
     repo: String,
     theme: Option<Theme>,
     show_owner: Option<bool>,
+    format: Option<Format>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -54,9 +57,10 @@ pub struct GistParams {
     id: String,
     theme: Option<Theme>,
     show_owner: Option<bool>,
+    format: Option<Format>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub enum HFPinIcon {
     #[serde(rename = "model")]
     Model,
@@ -84,8 +88,39 @@ pub struct HFTag {
     visible: bool,
 }
 
+// data of `format=json`, instead of raw Huggingface API response
+#[derive(Debug, Serialize)]
+pub struct HFPinData {
+    name: String,
+    description: Option<String>,
+    #[serde(rename = "type")]
+    typename: HFPinIcon,
+    likes: u32,
+    downloads: Option<u32>,
+    tags: Vec<String>,
+}
+
+impl From<HFRepoData> for HFPinData {
+    fn from(raw_data: HFRepoData) -> Self {
+        let downloads = match &raw_data {
+            HFRepoData::Model(model) => Some(model.downloads),
+            HFRepoData::Dataset(dataset) => Some(dataset.downloads),
+            HFRepoData::Space(_) => None,
+        };
+
+        HFPinData {
+            name: raw_data.get_id(),
+            description: raw_data.get_description(),
+            typename: get_hf_pin_icon(&raw_data),
+            likes: raw_data.get_base().likes,
+            downloads,
+            tags: get_hf_tags(&raw_data),
+        }
+    }
+}
+
 #[derive(Template)]
-#[template(path = "compact/pin/huggingface.html")]
+#[template(path = "compact/pin/huggingface.html", escape = "svg")]
 pub struct HFPinTemplate<'a> {
     name: String,
     desc: String,
@@ -122,7 +157,7 @@ pub struct GHLangText {
 }
 
 #[derive(Template)]
-#[template(path = "compact/pin/github.html")]
+#[template(path = "compact/pin/github.html", escape = "svg")]
 pub struct GHPinTemplate<'a> {
     name: String,
     desc: String,
@@ -139,22 +174,22 @@ pub struct GHPinTemplate<'a> {
 }
 
 pub async fn huggingface_get_data(
-    username: &String,
-    repo: &String,
-    typename: &HFPinIcon,
+    username: String,
+    repo: String,
+    typename: HFPinIcon,
 ) -> Result<HFRepoData, PreparedTemplate> {
     let data = match typename {
-        HFPinIcon::Model => huggingface::get_model(username, repo)
+        HFPinIcon::Model => huggingface::get_model(&username, &repo)
             .await
             .map(HFRepoResponse::Model),
-        HFPinIcon::Dataset => huggingface::get_dataset(username, repo)
+        HFPinIcon::Dataset => huggingface::get_dataset(&username, &repo)
             .await
             .map(HFRepoResponse::Dataset),
-        HFPinIcon::Space => huggingface::get_space(username, repo)
+        HFPinIcon::Space => huggingface::get_space(&username, &repo)
             .await
             .map(HFRepoResponse::Space),
     };
-    if !data.is_ok() {
+    if data.is_err() {
         return Err(PreparedTemplate::Unknown);
     }
 
@@ -171,49 +206,36 @@ pub async fn huggingface_get_data(
 }
 
 pub async fn get_huggingface_pin_impl(
-    cache: Cache<String, String>,
+    cache: CardCache,
     username: &String,
     repo: &String,
     typename: &HFPinIcon,
-) -> Result<HFRepoData, PreparedTemplate> {
+) -> Result<Cached<HFRepoData>, PreparedTemplate> {
     if username.is_empty() || repo.is_empty() {
         return Err(PreparedTemplate::FailedFindRepo);
     }
 
     let cache_key = format!("huggingface:{:?}:{username}:{repo}", typename);
-    if let Some(cached) = cache.get(&cache_key).await {
-        let data = serde_json::from_str(&cached).unwrap();
-        return Ok(data);
-    }
-
-    let data = huggingface_get_data(username, repo, typename).await;
-    let result = match data {
-        Ok(data) => data,
-        Err(err) => return Err(err),
-    };
-
-    let cache_body = serde_json::to_string(&result).unwrap();
-    cache.insert(cache_key, cache_body).await;
-
-    Ok(result)
+    cache
+        .get_or_fetch(
+            cache_key,
+            huggingface_get_data(username.clone(), repo.clone(), typename.clone()),
+        )
+        .await
 }
 
-pub fn render_huggingface_pin(
-    username: String,
-    repo: String,
-    show_owner: bool,
-    theme: Theme,
-    pin_data: Result<HFRepoData, PreparedTemplate>,
-) -> Response {
-    let raw_data = match pin_data {
-        Ok(data) => data,
-        Err(err) => return err.render(),
-    };
+fn get_hf_pin_icon(raw_data: &HFRepoData) -> HFPinIcon {
+    match raw_data {
+        HFRepoData::Model(_) => HFPinIcon::Model,
+        HFRepoData::Dataset(_) => HFPinIcon::Dataset,
+        HFRepoData::Space(_) => HFPinIcon::Space,
+    }
+}
 
+// tags shown on the pin, e.g. model type, pipeline tag and license
+fn get_hf_tags(raw_data: &HFRepoData) -> Vec<String> {
     let mut raw_tags: Vec<String> = vec![];
-    let downloads_raw: &Option<String> = &raw_data.get_downloads_count();
-    let downloads: Option<&String> = downloads_raw.as_ref();
-    if let HFRepoData::Model(model) = &raw_data {
+    if let HFRepoData::Model(model) = raw_data {
         if let Some(model_type) = model
             .config
             .as_ref()
@@ -227,41 +249,57 @@ pub fn render_huggingface_pin(
         }
     }
 
-    if let HFRepoData::Dataset(dataset) = &raw_data {
-        if let Some(task_category) = &dataset
+    if let HFRepoData::Dataset(dataset) = raw_data
+        && let Some(task_category) = &dataset
             .base
             .card_data
             .task_categories
             .as_ref()
             .and_then(|categories| categories.first())
-        {
-            raw_tags.push((*task_category).clone())
-        }
+    {
+        raw_tags.push((*task_category).clone())
     }
 
-    if let HFRepoData::Space(space) = &raw_data {
+    if let HFRepoData::Space(space) = raw_data {
         let running_on = space.runtime.hardware.current.to_string();
         raw_tags.push(format!("Running on {running_on}"));
     }
 
-    let icon = match &raw_data {
-        HFRepoData::Model(_) => HFPinIcon::Model,
-        HFRepoData::Dataset(_) => HFPinIcon::Dataset,
-        HFRepoData::Space(_) => HFPinIcon::Space,
-    };
+    if let Some(license) = &raw_data.get_license()
+        && license != "other"
+    {
+        raw_tags.push(license.to_uppercase());
+    }
 
-    if let Some(license) = &raw_data.get_license() {
-        if license != "other" {
-            raw_tags.push(license.to_uppercase());
-        }
+    if raw_tags.is_empty()
+        && let Some(tag) = &raw_data.get_repo_tags().first()
+    {
+        raw_tags.push((*tag).clone());
     }
 
+    raw_tags
+}
+
+pub fn render_huggingface_pin(
+    username: String,
+    repo: String,
+    show_owner: bool,
+    theme: Theme,
+    pin_data: Result<Cached<HFRepoData>, PreparedTemplate>,
+) -> Response {
+    let Cached {
+        data: raw_data,
+        max_age,
+    } = match pin_data {
+        Ok(data) => data,
+        Err(err) => return err.render(),
+    };
+
+    let downloads_raw: &Option<String> = &raw_data.get_downloads_count();
+    let downloads: Option<&String> = downloads_raw.as_ref();
+    let raw_tags = get_hf_tags(&raw_data);
+    let icon = get_hf_pin_icon(&raw_data);
     let likes = &raw_data.get_likes();
-    if raw_tags.len() == 0 {
-        if let Some(tag) = &raw_data.get_repo_tags().first() {
-            raw_tags.push((*tag).clone());
-        }
-    }
 
     let mut translate_x: usize = 0;
     let tags: Vec<HFTag> = raw_tags
@@ -270,12 +308,12 @@ pub fn render_huggingface_pin(
             let width = calc_width(tag, 13.0) + 8;
             let tag_translate_x = translate_x;
             translate_x += width + 10;
-            return HFTag {
+            HFTag {
                 name: tag.clone(),
                 width,
                 translate_x: tag_translate_x,
                 visible: width + tag_translate_x <= MAX_PIN_WIDTH,
-            };
+            }
         })
         .filter(|tag| tag.visible)
         .collect();
@@ -298,50 +336,42 @@ pub fn render_huggingface_pin(
         theme_data,
     };
 
-    let svg_template = templates::SVGTemplate(template);
+    let svg_template = templates::SVGTemplate(template, max_age);
     templates::SVGTemplate::<HFPinTemplate>::into_response(svg_template)
 }
 
 pub async fn github_get_data(
-    username: &String,
-    repo: &String,
+    username: String,
+    repo: String,
 ) -> Result<Repository, PreparedTemplate> {
-    let data = github::get_repo(username, repo).await;
-    if !data.is_ok() {
+    let data = github::get_repo(&username, &repo).await;
+    if data.is_err() {
         return Err(PreparedTemplate::Unknown);
     }
 
     match data.unwrap() {
-        RestResponse::Failed(res) => return Err(gh_handle_error_template(res)),
+        RestResponse::Failed(res) => Err(gh_repo_handle_error_template(res)),
         RestResponse::Valid(res) => Ok(res),
     }
 }
 
 pub async fn get_github_pin_impl(
-    cache: Cache<String, String>,
+    cache: CardCache,
     username: &String,
     repo: &String,
-) -> Result<Repository, PreparedTemplate> {
-    if username.is_empty() || repo.is_empty() {
-        return Err(PreparedTemplate::FailedFindRepo);
+) -> Result<Cached<Repository>, PreparedTemplate> {
+    if !github::is_valid_login(username) {
+        return Err(PreparedTemplate::FailedFindUser);
     }
 
-    let cache_key = format!("github:repo:{username}:{repo}");
-    if let Some(cached) = cache.get(&cache_key).await {
-        let data = serde_json::from_str(&cached).unwrap();
-        return Ok(data);
+    if !github::is_valid_repo_name(repo) {
+        return Err(PreparedTemplate::FailedFindRepo);
     }
 
-    let data = github_get_data(username, repo).await;
-    let result = match data {
-        Ok(data) => data,
-        Err(err) => return Err(err),
-    };
-
-    let cache_body = serde_json::to_string(&result).unwrap();
-    cache.insert(cache_key, cache_body).await;
-
-    Ok(result)
+    let cache_key = format!("github:repo:{username}:{repo}");
+    cache
+        .get_or_fetch(cache_key, github_get_data(username.clone(), repo.clone()))
+        .await
 }
 
 pub fn render_github_pin(
@@ -349,9 +379,12 @@ pub fn render_github_pin(
     repo: String,
     show_owner: bool,
     theme: Theme,
-    repo_data: Result<Repository, PreparedTemplate>,
+    repo_data: Result<Cached<Repository>, PreparedTemplate>,
 ) -> Response {
-    let raw_data = match repo_data {
+    let Cached {
+        data: raw_data,
+        max_age,
+    } = match repo_data {
         Ok(data) => data,
         Err(err) => return err.render(),
     };
@@ -380,14 +413,11 @@ pub fn render_github_pin(
         Some(&forks_pretty)
     };
 
-    let language = match raw_data.language {
-        Some(lang) => Some(GHLangText {
-            color: get_lang_color(&lang),
-            width: calc_width(&lang, 13.0),
-            name: lang,
-        }),
-        None => None,
-    };
+    let language = raw_data.language.map(|lang| GHLangText {
+        color: get_lang_color(&lang),
+        width: calc_width(&lang, 13.0),
+        name: lang,
+    });
     let theme_data = theme.get_data();
     let is_single_text_row = rows.len() == 1;
 
@@ -413,53 +443,54 @@ pub fn render_github_pin(
         language,
         theme_data,
     };
-    let svg_template = templates::SVGTemplate(template);
+    let svg_template = templates::SVGTemplate(template, max_age);
     templates::SVGTemplate::<GHPinTemplate>::into_response(svg_template)
 }
 
 pub async fn get_huggingface_pin(
-    State(cache): State<Cache<String, String>>,
+    State(cache): State<CardCache>,
     Query(params): Query<HFParams>,
 ) -> Response {
     let theme = params.theme.unwrap_or(CONFIG.default_theme.clone());
     let username = params.username;
     let repo = params.repo;
     let typename = params.typename;
-    let show_owner = if let Some(show_owner) = params.show_owner {
-        show_owner
-    } else {
-        false
-    };
+    let show_owner = params.show_owner.unwrap_or_default();
     let repo_data = get_huggingface_pin_impl(cache, &username, &repo, &typename).await;
+    if params.format == Some(Format::Json) {
+        let pin_data = repo_data.map(|data| data.map(HFPinData::from));
+        return templates::JsonData(pin_data).into_response();
+    }
+
     render_huggingface_pin(username, repo, show_owner, theme, repo_data)
 }
 
 pub async fn get_github_repo_pin(
-    State(cache): State<Cache<String, String>>,
+    State(cache): State<CardCache>,
     Query(params): Query<GHParams>,
 ) -> Response {
     let theme = params.theme.unwrap_or(CONFIG.default_theme.clone());
     let username = params.username;
     let repo = params.repo;
-    let show_owner = if let Some(show_owner) = params.show_owner {
-        show_owner
-    } else {
-        false
-    };
+    let show_owner = params.show_owner.unwrap_or_default();
 
     let repo_data = get_github_pin_impl(cache, &username, &repo).await;
+    if params.format == Some(Format::Json) {
+        return templates::JsonData(repo_data).into_response();
+    }
+
     render_github_pin(username, repo, show_owner, theme, repo_data)
 }
 
-pub async fn gist_get_data(id: &String) -> Result<Gist, PreparedTemplate> {
-    let data = github::get_gist(id).await;
+pub async fn gist_get_data(id: String) -> Result<Gist, PreparedTemplate> {
+    let data = github::get_gist(&id).await;
 
-    if !data.is_ok() {
+    if data.is_err() {
         return Err(PreparedTemplate::Unknown);
     }
 
     match data.unwrap() {
-        GithubGistResponse::Failed(res) => return Err(gh_handle_error_template(res)),
+        GithubGistResponse::Failed(res) => Err(gh_handle_error_template(res)),
         GithubGistResponse::Valid(res) => match res.data.viewer.gist {
             None => Err(PreparedTemplate::FailedFindRepo),
             Some(gist_data) => Ok(gist_data),
@@ -468,38 +499,29 @@ pub async fn gist_get_data(id: &String) -> Result<Gist, PreparedTemplate> {
 }
 
 pub async fn get_gist_pin_impl(
-    cache: Cache<String, String>,
+    cache: CardCache,
     id: &String,
-) -> Result<Gist, PreparedTemplate> {
-    if id.is_empty() {
+) -> Result<Cached<Gist>, PreparedTemplate> {
+    if !github::is_valid_gist_id(id) {
         return Err(PreparedTemplate::FailedFindRepo);
     }
 
     let cache_key = format!("github:gist:{id}");
-    if let Some(cached) = cache.get(&cache_key).await {
-        let data = serde_json::from_str(&cached).unwrap();
-        return Ok(data);
-    }
-
-    let data = gist_get_data(id).await;
-    let result = match data {
-        Ok(data) => data,
-        Err(err) => return Err(err),
-    };
-
-    let cache_body = serde_json::to_string(&result).unwrap();
-    cache.insert(cache_key, cache_body).await;
-
-    Ok(result)
+    cache
+        .get_or_fetch(cache_key, gist_get_data(id.clone()))
+        .await
 }
 
 pub fn render_github_gist(
     gist_id: String,
     show_owner: bool,
     theme: Theme,
-    repo_data: Result<Gist, PreparedTemplate>,
+    repo_data: Result<Cached<Gist>, PreparedTemplate>,
 ) -> Response {
-    let raw_data = match repo_data {
+    let Cached {
+        data: raw_data,
+        max_age,
+    } = match repo_data {
         Ok(data) => data,
         Err(err) => return err.render(),
     };
@@ -566,22 +588,151 @@ pub fn render_github_gist(
         language,
         theme_data,
     };
-    let svg_template = templates::SVGTemplate(template);
+    let svg_template = templates::SVGTemplate(template, max_age);
     templates::SVGTemplate::<GHPinTemplate>::into_response(svg_template)
 }
 
 pub async fn get_github_gist_pin(
-    State(cache): State<Cache<String, String>>,
+    State(cache): State<CardCache>,
     Query(params): Query<GistParams>,
 ) -> Response {
     let theme = params.theme.unwrap_or(CONFIG.default_theme.clone());
     let id = params.id;
-    let show_owner = if let Some(show_owner) = params.show_owner {
-        show_owner
-    } else {
-        false
-    };
+    let show_owner = params.show_owner.unwrap_or_default();
 
     let repo_data = get_gist_pin_impl(cache, &id).await;
+    if params.format == Some(Format::Json) {
+        return templates::JsonData(repo_data).into_response();
+    }
+
     render_github_gist(id, show_owner, theme, repo_data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::svg::{calc_width_uncached, parse_svg_texts, wrap_text_with};
+    use std::time::{Duration, Instant};
+
+    const BENCH_ITERATIONS: u32 = 1000;
+    // font parsing on every measure is too slow for many runs
+    const UNCACHED_BENCH_ITERATIONS: u32 = 10;
+    const DESCRIPTION: &str = "Beautiful and fast stats cards for your GitHub profile README, \
+        rendered on the fly with cached glyph widths and fallback fonts";
+
+    // average time of `f` over `iterations` runs after a warm-up run,
+    // run with `cargo test --release -- --ignored --nocapture bench_`
+    fn bench<T>(name: &str, iterations: u32, mut f: impl FnMut() -> T) {
+        f();
+        let start = Instant::now();
+        for _ in 0..iterations {
+            std::hint::black_box(f());
+        }
+        let avg = start.elapsed() / iterations;
+        println!("{name}: {avg:?} per iteration");
+    }
+
+    // cached vs uncached measuring, e.g. ~60µs vs ~800ms in release build
+    #[test]
+    #[ignore = "timing benchmark"]
+    fn bench_wrap_text() {
+        bench("wrap_text", BENCH_ITERATIONS, || {
+            wrap_text(DESCRIPTION, 13.0, 365)
+        });
+        bench("wrap_text uncached", UNCACHED_BENCH_ITERATIONS, || {
+            wrap_text_with(DESCRIPTION, 13.0, 365, calc_width_uncached)
+        });
+    }
+
+    #[test]
+    #[ignore = "timing benchmark"]
+    fn bench_render_github_pin() {
+        bench("render_github_pin", BENCH_ITERATIONS, || {
+            let repo = Repository {
+                name: "stats-cards".to_string(),
+                description: Some(DESCRIPTION.to_string()),
+                language: Some("Rust".to_string()),
+                stargazers_count: 1234,
+                forks_count: 56,
+            };
+            render_github_pin(
+                "octocat".to_string(),
+                "stats-cards".to_string(),
+                true,
+                Theme::Dark,
+                Ok(Cached {
+                    data: repo,
+                    max_age: Duration::from_secs(60),
+                }),
+            )
+        });
+    }
+
+    #[test]
+    fn escapes_user_text() {
+        let stars = "<1>".to_string();
+        let template = GHPinTemplate {
+            name: "<a>&\"'\u{1}".to_string(),
+            desc: "<desc>".to_string(),
+            repo_text: "<a>&\"'".to_string(),
+            icon: GHPinIcon::Repo,
+            rows: vec!["<script>&amp;".to_string()],
+            language: Some(GHLangText {
+                name: "C<>".to_string(),
+                width: 20,
+                color: "#fff".to_string(),
+            }),
+            stars: Some(&stars),
+            forks: None,
+            is_single_text_row: true,
+            meta_counters_x_indent: 0,
+            forks_counter_x_indent: 0,
+            theme_data: Theme::Dark.get_data(),
+        };
+        let texts = parse_svg_texts(&template.render().unwrap());
+
+        assert!(texts.contains(&"<a>&\"': <desc>".to_string()));
+        assert!(texts.contains(&"<script>&amp;".to_string()));
+        assert!(texts.contains(&"C<>".to_string()));
+        assert!(texts.contains(&"<1>".to_string()));
+    }
+
+    #[test]
+    fn huggingface_json_data_is_normalized() {
+        let raw_data: HFRepoData = serde_json::from_str(
+            r#"{
+                "_id": "1", "id": "user/dataset", "private": false, "tags": ["text"],
+                "likes": 1234, "author": "user", "lastModified": "", "disabled": false,
+                "cardData": { "license": "mit", "task_categories": ["translation"] },
+                "createdAt": "", "downloads": 56, "description": "Parallel corpus"
+            }"#,
+        )
+        .unwrap();
+        let data = serde_json::to_value(HFPinData::from(raw_data)).unwrap();
+
+        assert_eq!(
+            data,
+            serde_json::json!({
+                "name": "user/dataset",
+                "description": "Parallel corpus",
+                "type": "dataset",
+                "likes": 1234,
+                "downloads": 56,
+                "tags": ["translation", "MIT"],
+            })
+        );
+    }
+
+    #[test]
+    fn missing_repo_is_not_found() {
+        let res: RestResponse<Repository> =
+            serde_json::from_str(r#"{"message":"Not Found","status":"404"}"#).unwrap();
+        let RestResponse::Failed(err) = res else {
+            panic!("expected failed response");
+        };
+        let template = gh_repo_handle_error_template(err);
+
+        assert!(matches!(template, PreparedTemplate::FailedFindRepo));
+        assert_eq!(template.status(), axum::http::StatusCode::NOT_FOUND);
+    }
+}
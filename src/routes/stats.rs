@@ -0,0 +1,308 @@
+use crate::api::github::{
+    self, StarsResponse as GithubStarsResponse, StatsRepositories,
+    StatsResponse as GithubStatsResponse,
+};
+use crate::data::config::CONFIG;
+use crate::data::theme::{Theme, ThemeData};
+use crate::prepared_templates::{PreparedTemplate, gh_handle_error_template};
+use crate::templates::{self, Format};
+use crate::utils::cache::{Cached, CardCache};
+use crate::utils::utils::fmt_num;
+
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+};
+use chrono::{Datelike, Utc};
+use serde::{Deserialize, Serialize};
+
+const ROW_HEIGHT: u32 = 25;
+const ROWS_START_Y: u32 = 60;
+const RANK_CIRCLE_RADIUS: f32 = 40.0;
+const MIN_CARD_HEIGHT: u32 = 150;
+// stars are summed over first 1000 repos
+const MAX_REPOS_PAGES: usize = 10;
+
+// same medians and weights as github-readme-stats
+const COMMITS_MEDIAN: f64 = 250.0;
+const COMMITS_WEIGHT: f64 = 2.0;
+const PRS_MEDIAN: f64 = 50.0;
+const PRS_WEIGHT: f64 = 3.0;
+const ISSUES_MEDIAN: f64 = 25.0;
+const ISSUES_WEIGHT: f64 = 1.0;
+const REVIEWS_MEDIAN: f64 = 2.0;
+const REVIEWS_WEIGHT: f64 = 1.0;
+const STARS_MEDIAN: f64 = 50.0;
+const STARS_WEIGHT: f64 = 4.0;
+const FOLLOWERS_MEDIAN: f64 = 10.0;
+const FOLLOWERS_WEIGHT: f64 = 1.0;
+
+const RANK_THRESHOLDS: [f64; 9] = [1.0, 12.5, 25.0, 37.5, 50.0, 62.5, 75.0, 87.5, 100.0];
+const RANK_LEVELS: [&str; 9] = ["S", "A+", "A", "A-", "B+", "B", "B-", "C+", "C"];
+
+#[derive(Deserialize, Serialize)]
+pub struct Params {
+    username: String,
+    theme: Option<Theme>,
+    // comma separated list of rows, e.g. hide=reviews,contribs
+    hide: Option<String>,
+    format: Option<Format>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Rank {
+    level: String,
+    percentile: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GithubStats {
+    name: String,
+    total_stars: u32,
+    total_commits: u32,
+    total_prs: u32,
+    total_issues: u32,
+    total_reviews: u32,
+    contributed_to: u32,
+    followers: u32,
+    rank: Rank,
+}
+
+#[derive(Debug)]
+pub struct StatsRow {
+    y: u32,
+    label: &'static str,
+    value: String,
+}
+
+#[derive(Template)]
+#[template(path = "compact/stats.html", escape = "svg")]
+pub struct CompactStatsTemplate {
+    name: String,
+    rows: Vec<StatsRow>,
+    rank: Rank,
+    rank_y: u32,
+    rank_radius: f32,
+    rank_circumference: f32,
+    rank_offset: f32,
+    height: u32,
+    theme_data: ThemeData,
+}
+
+fn exponential_cdf(x: f64) -> f64 {
+    1.0 - 2.0_f64.powf(-x)
+}
+
+fn log_normal_cdf(x: f64) -> f64 {
+    x / (1.0 + x)
+}
+
+pub fn calc_rank(
+    commits: u32,
+    prs: u32,
+    issues: u32,
+    reviews: u32,
+    stars: u32,
+    followers: u32,
+) -> Rank {
+    let total_weight = COMMITS_WEIGHT
+        + PRS_WEIGHT
+        + ISSUES_WEIGHT
+        + REVIEWS_WEIGHT
+        + STARS_WEIGHT
+        + FOLLOWERS_WEIGHT;
+    let score = COMMITS_WEIGHT * exponential_cdf(commits as f64 / COMMITS_MEDIAN)
+        + PRS_WEIGHT * exponential_cdf(prs as f64 / PRS_MEDIAN)
+        + ISSUES_WEIGHT * exponential_cdf(issues as f64 / ISSUES_MEDIAN)
+        + REVIEWS_WEIGHT * exponential_cdf(reviews as f64 / REVIEWS_MEDIAN)
+        + STARS_WEIGHT * log_normal_cdf(stars as f64 / STARS_MEDIAN)
+        + FOLLOWERS_WEIGHT * log_normal_cdf(followers as f64 / FOLLOWERS_MEDIAN);
+
+    let percentile = (1.0 - score / total_weight) * 100.0;
+    let level_idx = RANK_THRESHOLDS
+        .iter()
+        .position(|threshold| percentile <= *threshold)
+        .unwrap_or(RANK_LEVELS.len() - 1);
+
+    Rank {
+        level: RANK_LEVELS[level_idx].to_string(),
+        percentile,
+    }
+}
+
+// repos are ordered by stars, so next pages are requested until repo without stars
+async fn stars_github_get_data(
+    username: &String,
+    first_page: StatsRepositories,
+) -> Result<u32, PreparedTemplate> {
+    let mut repositories = first_page;
+    let mut total_stars = 0;
+    for page in 1..=MAX_REPOS_PAGES {
+        total_stars += repositories
+            .nodes
+            .iter()
+            .map(|repo| repo.stargazer_count)
+            .sum::<u32>();
+        let has_stars = repositories
+            .nodes
+            .last()
+            .is_some_and(|repo| repo.stargazer_count > 0);
+        let page_info = repositories.page_info;
+        if page == MAX_REPOS_PAGES || !page_info.has_next_page || !has_stars {
+            break;
+        }
+
+        let Some(after) = page_info.end_cursor else {
+            break;
+        };
+
+        let stars = github::get_stars(username, &after).await;
+        if stars.is_err() {
+            return Err(PreparedTemplate::Unknown);
+        }
+
+        repositories = match stars.unwrap() {
+            GithubStarsResponse::Failed(err) => return Err(gh_handle_error_template(err)),
+            GithubStarsResponse::Valid(res) => match res.data.user {
+                None => return Err(PreparedTemplate::FailedFindUser),
+                Some(user_data) => user_data.repositories,
+            },
+        };
+    }
+
+    Ok(total_stars)
+}
+
+async fn stats_github_get_data(username: String) -> Result<GithubStats, PreparedTemplate> {
+    // commits are counted only for the current year
+    let start_date = format!("{}-01-01T00:00:00.000Z", Utc::now().year());
+    let stats = github::get_stats(&username, &start_date).await;
+    if stats.is_err() {
+        return Err(PreparedTemplate::Unknown);
+    }
+
+    let user = match stats.unwrap() {
+        GithubStatsResponse::Failed(err) => return Err(gh_handle_error_template(err)),
+        GithubStatsResponse::Valid(res) => match res.data.user {
+            None => return Err(PreparedTemplate::FailedFindUser),
+            Some(user_data) => user_data,
+        },
+    };
+
+    let total_stars = stars_github_get_data(&username, user.repositories).await?;
+    let contributions = user.contributions_collection;
+    let rank = calc_rank(
+        contributions.total_commit_contributions,
+        user.pull_requests.total_count,
+        user.issues.total_count,
+        contributions.total_pull_request_review_contributions,
+        total_stars,
+        user.followers.total_count,
+    );
+    let stats = GithubStats {
+        name: user
+            .name
+            .filter(|name| !name.is_empty())
+            .unwrap_or(user.login),
+        total_stars,
+        total_commits: contributions.total_commit_contributions,
+        total_prs: user.pull_requests.total_count,
+        total_issues: user.issues.total_count,
+        total_reviews: contributions.total_pull_request_review_contributions,
+        contributed_to: user.repositories_contributed_to.total_count,
+        followers: user.followers.total_count,
+        rank,
+    };
+
+    Ok(stats)
+}
+
+async fn get_stats_github_intl(
+    cache: CardCache,
+    username: &String,
+) -> Result<Cached<GithubStats>, PreparedTemplate> {
+    if !github::is_valid_login(username) {
+        return Err(PreparedTemplate::FailedFindUser);
+    }
+
+    let cache_key = format!("github:stats:{username}");
+    cache
+        .get_or_fetch(cache_key, stats_github_get_data(username.clone()))
+        .await
+}
+
+pub fn render_stats(
+    hide: Vec<String>,
+    theme: Theme,
+    stats_res: Result<Cached<GithubStats>, PreparedTemplate>,
+) -> Response {
+    let Cached {
+        data: stats,
+        max_age,
+    } = match stats_res {
+        Ok(stats) => stats,
+        Err(err) => return err.render(),
+    };
+
+    let rows_data = [
+        ("stars", "Total Stars Earned", stats.total_stars),
+        ("commits", "Total Commits (this year)", stats.total_commits),
+        ("prs", "Total PRs", stats.total_prs),
+        ("issues", "Total Issues", stats.total_issues),
+        ("reviews", "Total PR Reviews", stats.total_reviews),
+        (
+            "contribs",
+            "Contributed to (last year)",
+            stats.contributed_to,
+        ),
+    ];
+    let rows: Vec<StatsRow> = rows_data
+        .iter()
+        .filter(|(key, _, _)| !hide.iter().any(|hidden| hidden == key))
+        .enumerate()
+        .map(|(idx, (_, label, value))| StatsRow {
+            y: ROWS_START_Y + idx as u32 * ROW_HEIGHT,
+            label,
+            value: fmt_num(*value as i32),
+        })
+        .collect();
+
+    let height = (ROWS_START_Y + rows.len() as u32 * ROW_HEIGHT).max(MIN_CARD_HEIGHT);
+    let rank_circumference = 2.0 * std::f32::consts::PI * RANK_CIRCLE_RADIUS;
+    let rank_offset = rank_circumference * (stats.rank.percentile as f32 / 100.0);
+
+    let template = CompactStatsTemplate {
+        name: stats.name,
+        rows,
+        rank: stats.rank,
+        rank_y: height / 2 + 10,
+        rank_radius: RANK_CIRCLE_RADIUS,
+        rank_circumference,
+        rank_offset,
+        height,
+        theme_data: theme.get_data(),
+    };
+    let svg_template = templates::SVGTemplate(template, max_age);
+    templates::SVGTemplate::<CompactStatsTemplate>::into_response(svg_template)
+}
+
+pub async fn get_github_stats(
+    State(cache): State<CardCache>,
+    Query(params): Query<Params>,
+) -> Response {
+    let theme = params.theme.unwrap_or(CONFIG.default_theme.clone());
+    let hide: Vec<String> = params
+        .hide
+        .unwrap_or_default()
+        .split(',')
+        .map(|row| row.trim().to_lowercase())
+        .filter(|row| !row.is_empty())
+        .collect();
+    let stats_res = get_stats_github_intl(cache, &params.username).await;
+    if params.format == Some(Format::Json) {
+        return templates::JsonData(stats_res).into_response();
+    }
+
+    render_stats(hide, theme, stats_res)
+}
@@ -0,0 +1,571 @@
+use crate::api::github::{self, ActivityResponse as GithubActivityResponse};
+use crate::api::wakatime::{self, SummariesResponse as WakaTimeSummariesResponse};
+use crate::data::config::CONFIG;
+use crate::data::theme::{Theme, ThemeData};
+use crate::prepared_templates::{PreparedTemplate, gh_handle_error_template};
+use crate::templates::{self, Format};
+use crate::utils::cache::{Cached, CardCache};
+
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// github allows to request max 1 year of contributions at once
+const MAX_PERIOD_DAYS: i64 = 365;
+const STREAK_COLUMN_WIDTH: u32 = 150;
+
+#[derive(Deserialize, Serialize)]
+pub struct Params {
+    username: String,
+    theme: Option<Theme>,
+    format: Option<Format>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WakaParams {
+    username: String,
+    theme: Option<Theme>,
+    // min coding time in minutes to count a day in streak
+    threshold: Option<u32>,
+    format: Option<Format>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct Streak {
+    length: u32,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StreakStats {
+    total_contributions: i32,
+    first_contribution: Option<NaiveDate>,
+    current_streak: Streak,
+    longest_streak: Streak,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CodingDay {
+    date: NaiveDate,
+    total_seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodingStreak {
+    #[serde(flatten)]
+    stats: StreakStats,
+    best_day: Option<CodingDay>,
+}
+
+#[derive(Debug)]
+pub struct StreakColumn {
+    x: u32,
+    value: String,
+    label: &'static str,
+    range: String,
+}
+
+#[derive(Template)]
+#[template(path = "compact/streak.html", escape = "svg")]
+pub struct CompactStreakTemplate {
+    name: String,
+    title: &'static str,
+    columns: Vec<StreakColumn>,
+    theme_data: ThemeData,
+}
+
+fn fmt_date(date: &NaiveDate) -> String {
+    date.format("%b %-d, %Y").to_string()
+}
+
+fn fmt_duration(total_seconds: f64) -> String {
+    let minutes = (total_seconds / 60.0) as u32;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+fn fmt_streak_range(streak: &Streak) -> String {
+    match (streak.start, streak.end) {
+        (Some(start), Some(end)) if start == end => fmt_date(&start),
+        (Some(start), Some(end)) => format!("{} - {}", fmt_date(&start), fmt_date(&end)),
+        _ => "No contributions yet".to_string(),
+    }
+}
+
+pub fn calc_streak_stats(days: &BTreeMap<NaiveDate, i32>) -> StreakStats {
+    let total_contributions = days.values().sum();
+    let first_contribution = days
+        .iter()
+        .find(|(_, count)| **count > 0)
+        .map(|(date, _)| *date);
+
+    let mut longest_streak = Streak::default();
+    let mut streak = Streak::default();
+    let mut prev_date: Option<NaiveDate> = None;
+    for (date, count) in days {
+        let is_next_day = prev_date.is_some_and(|prev| *date - prev == Duration::days(1));
+        prev_date = Some(*date);
+        if *count == 0 {
+            streak = Streak::default();
+            continue;
+        }
+
+        if !is_next_day || streak.length == 0 {
+            streak = Streak {
+                length: 0,
+                start: Some(*date),
+                end: None,
+            };
+        }
+
+        streak.length += 1;
+        streak.end = Some(*date);
+        if streak.length > longest_streak.length {
+            longest_streak = streak.clone();
+        }
+    }
+
+    // today without contributions doesn't break current streak yet
+    let mut current_streak = Streak::default();
+    let mut days_rev = days.iter().rev().peekable();
+    if days_rev.peek().is_some_and(|(_, count)| **count == 0) {
+        days_rev.next();
+    }
+
+    for (date, count) in days_rev {
+        if *count == 0 {
+            break;
+        }
+
+        if current_streak
+            .start
+            .is_some_and(|start| start - *date != Duration::days(1))
+        {
+            break;
+        }
+
+        current_streak.length += 1;
+        current_streak.start = Some(*date);
+        if current_streak.end.is_none() {
+            current_streak.end = Some(*date);
+        }
+    }
+
+    StreakStats {
+        total_contributions,
+        first_contribution,
+        current_streak,
+        longest_streak,
+    }
+}
+
+async fn streak_github_get_data(username: String) -> Result<StreakStats, PreparedTemplate> {
+    let mut days: BTreeMap<NaiveDate, i32> = BTreeMap::new();
+    let mut created_at: Option<NaiveDate> = None;
+    let mut end_date = Utc::now().date_naive();
+    loop {
+        let mut start_date = end_date - Duration::days(MAX_PERIOD_DAYS - 1);
+        if let Some(created_at) = created_at {
+            start_date = start_date.max(created_at);
+        }
+
+        let start = format!("{start_date}T00:00:00.000Z");
+        let end = format!("{end_date}T23:59:59.999Z");
+        let stats = github::get_activity(&username, &start, &end).await;
+        if stats.is_err() {
+            return Err(PreparedTemplate::Unknown);
+        }
+
+        let user = match stats.unwrap() {
+            GithubActivityResponse::Failed(err) => return Err(gh_handle_error_template(err)),
+            GithubActivityResponse::Valid(res) => match res.data.user {
+                None => return Err(PreparedTemplate::FailedFindUser),
+                Some(user_data) => user_data,
+            },
+        };
+
+        let user_created_at = match DateTime::parse_from_rfc3339(&user.created_at) {
+            Ok(date) => date.date_naive(),
+            Err(_) => return Err(PreparedTemplate::Unknown),
+        };
+        created_at = Some(user_created_at);
+
+        let weeks = user.contributions_collection.contribution_calendar.weeks;
+        for day in weeks.into_iter().flat_map(|week| week.contribution_days) {
+            if let Ok(date) = NaiveDate::parse_from_str(&day.date, "%Y-%m-%d")
+                && date >= start_date
+                && date <= end_date
+            {
+                days.insert(date, day.contribution_count);
+            }
+        }
+
+        if start_date <= user_created_at {
+            break;
+        }
+
+        end_date = start_date - Duration::days(1);
+    }
+
+    Ok(calc_streak_stats(&days))
+}
+
+async fn get_streak_github_intl(
+    cache: CardCache,
+    username: &String,
+) -> Result<Cached<StreakStats>, PreparedTemplate> {
+    if !github::is_valid_login(username) {
+        return Err(PreparedTemplate::FailedFindUser);
+    }
+
+    let cache_key = format!("github:streak:{username}");
+    cache
+        .get_or_fetch(cache_key, streak_github_get_data(username.clone()))
+        .await
+}
+
+pub fn render_streak(
+    username: String,
+    theme: Theme,
+    streak_res: Result<Cached<StreakStats>, PreparedTemplate>,
+) -> Response {
+    let Cached {
+        data: stats,
+        max_age,
+    } = match streak_res {
+        Ok(stats) => stats,
+        Err(err) => return err.render(),
+    };
+
+    let total_range = match stats.first_contribution {
+        Some(date) => format!("{} - Present", fmt_date(&date)),
+        None => "No contributions yet".to_string(),
+    };
+    let columns = vec![
+        StreakColumn {
+            x: STREAK_COLUMN_WIDTH / 2,
+            value: stats.total_contributions.to_string(),
+            label: "Total Contributions",
+            range: total_range,
+        },
+        StreakColumn {
+            x: STREAK_COLUMN_WIDTH + STREAK_COLUMN_WIDTH / 2,
+            value: stats.current_streak.length.to_string(),
+            label: "Current Streak",
+            range: fmt_streak_range(&stats.current_streak),
+        },
+        StreakColumn {
+            x: STREAK_COLUMN_WIDTH * 2 + STREAK_COLUMN_WIDTH / 2,
+            value: stats.longest_streak.length.to_string(),
+            label: "Longest Streak",
+            range: fmt_streak_range(&stats.longest_streak),
+        },
+    ];
+
+    let template = CompactStreakTemplate {
+        name: username,
+        title: "Contribution Streak",
+        columns,
+        theme_data: theme.get_data(),
+    };
+    let svg_template = templates::SVGTemplate(template, max_age);
+    templates::SVGTemplate::<CompactStreakTemplate>::into_response(svg_template)
+}
+
+// summaries endpoint isn't public, so it's requested with the user's api key
+async fn coding_days_waka_get_data(
+    username: String,
+    api_key: String,
+) -> Result<Vec<CodingDay>, PreparedTemplate> {
+    let end_date = Utc::now().date_naive();
+    let start_date = end_date - Duration::days(MAX_PERIOD_DAYS - 1);
+    let summaries = wakatime::get_summaries(
+        &CONFIG.wakatime_api_url,
+        &username,
+        &start_date.to_string(),
+        &end_date.to_string(),
+        Some(&api_key),
+    )
+    .await;
+    if summaries.is_err() {
+        return Err(PreparedTemplate::Unknown);
+    }
+
+    let summaries_data = match summaries.unwrap() {
+        WakaTimeSummariesResponse::Failed(err) => {
+            let err_template = match err.error.as_str() {
+                "Not found." => PreparedTemplate::FailedFindUser,
+                _ => PreparedTemplate::FailedFindActivity,
+            };
+            return Err(err_template);
+        }
+        WakaTimeSummariesResponse::Valid(res) => res.data,
+    };
+
+    let days: Vec<CodingDay> = summaries_data
+        .iter()
+        .filter_map(|summary| {
+            let date = NaiveDate::parse_from_str(&summary.range.date, "%Y-%m-%d").ok()?;
+            Some(CodingDay {
+                date,
+                total_seconds: summary.grand_total.total_seconds,
+            })
+        })
+        .collect();
+
+    Ok(days)
+}
+
+async fn get_coding_days_waka_intl(
+    cache: CardCache,
+    username: &String,
+) -> Result<Cached<Vec<CodingDay>>, PreparedTemplate> {
+    if !wakatime::is_valid_username(username) {
+        return Err(PreparedTemplate::FailedFindUser);
+    }
+
+    let Some(api_key) = wakatime::get_api_key(username) else {
+        return Err(PreparedTemplate::APIKeyRequired);
+    };
+
+    let cache_key = format!("wakatime:days:{username}");
+    cache
+        .get_or_fetch(
+            cache_key,
+            coding_days_waka_get_data(username.clone(), api_key),
+        )
+        .await
+}
+
+pub fn calc_coding_streak(days: Vec<CodingDay>, threshold: u32) -> CodingStreak {
+    let threshold_seconds = threshold as f64 * 60.0;
+    let active_days: BTreeMap<NaiveDate, i32> = days
+        .iter()
+        .map(|day| {
+            let is_active = day.total_seconds > 0.0 && day.total_seconds >= threshold_seconds;
+            (day.date, is_active as i32)
+        })
+        .collect();
+    let stats = calc_streak_stats(&active_days);
+    let best_day = days
+        .into_iter()
+        .filter(|day| day.total_seconds > 0.0)
+        .max_by(|a, b| a.total_seconds.total_cmp(&b.total_seconds));
+
+    CodingStreak { stats, best_day }
+}
+
+pub fn render_coding_streak(
+    username: String,
+    theme: Theme,
+    streak_res: Result<Cached<CodingStreak>, PreparedTemplate>,
+) -> Response {
+    let Cached {
+        data: CodingStreak { stats, best_day },
+        max_age,
+    } = match streak_res {
+        Ok(streak) => streak,
+        Err(err) => return err.render(),
+    };
+
+    let columns = vec![
+        StreakColumn {
+            x: STREAK_COLUMN_WIDTH / 2,
+            value: stats.current_streak.length.to_string(),
+            label: "Current Streak",
+            range: fmt_streak_range(&stats.current_streak),
+        },
+        StreakColumn {
+            x: STREAK_COLUMN_WIDTH + STREAK_COLUMN_WIDTH / 2,
+            value: stats.longest_streak.length.to_string(),
+            label: "Best Streak",
+            range: fmt_streak_range(&stats.longest_streak),
+        },
+        StreakColumn {
+            x: STREAK_COLUMN_WIDTH * 2 + STREAK_COLUMN_WIDTH / 2,
+            value: match &best_day {
+                Some(day) => fmt_duration(day.total_seconds),
+                None => "0m".to_string(),
+            },
+            label: "Best Day",
+            range: match &best_day {
+                Some(day) => fmt_date(&day.date),
+                None => "No coding activity yet".to_string(),
+            },
+        },
+    ];
+
+    let template = CompactStreakTemplate {
+        name: username,
+        title: "Coding Streak",
+        columns,
+        theme_data: theme.get_data(),
+    };
+    let svg_template = templates::SVGTemplate(template, max_age);
+    templates::SVGTemplate::<CompactStreakTemplate>::into_response(svg_template)
+}
+
+pub async fn get_github_streak(
+    State(cache): State<CardCache>,
+    Query(params): Query<Params>,
+) -> Response {
+    let username = params.username;
+    let theme = params.theme.unwrap_or(CONFIG.default_theme.clone());
+    let streak_res = get_streak_github_intl(cache, &username).await;
+    if params.format == Some(Format::Json) {
+        return templates::JsonData(streak_res).into_response();
+    }
+
+    render_streak(username, theme, streak_res)
+}
+
+pub async fn get_waka_streak(
+    State(cache): State<CardCache>,
+    Query(params): Query<WakaParams>,
+) -> Response {
+    let username = params.username;
+    let theme = params.theme.unwrap_or(CONFIG.default_theme.clone());
+    let threshold = params.threshold.unwrap_or_default();
+    let days_res = get_coding_days_waka_intl(cache, &username).await;
+    let streak_res = days_res.map(|days| days.map(|days| calc_coding_streak(days, threshold)));
+    if params.format == Some(Format::Json) {
+        return templates::JsonData(streak_res).into_response();
+    }
+
+    render_coding_streak(username, theme, streak_res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::svg::parse_svg_texts;
+
+    #[test]
+    fn escapes_user_text() {
+        let template = CompactStreakTemplate {
+            name: "<a>&\"'\u{1}".to_string(),
+            title: "Coding Streak",
+            columns: vec![StreakColumn {
+                x: 75,
+                value: "<b>".to_string(),
+                label: "Current Streak",
+                range: "&'".to_string(),
+            }],
+            theme_data: Theme::Dark.get_data(),
+        };
+        let texts = parse_svg_texts(&template.render().unwrap());
+
+        assert!(texts.contains(&"<a>&\"''s coding streak".to_string()));
+        assert!(texts.contains(&"<b>".to_string()));
+        assert!(texts.contains(&"&'".to_string()));
+    }
+
+    #[test]
+    fn coding_streak_with_huge_threshold() {
+        let today = Utc::now().date_naive();
+        let days = vec![CodingDay {
+            date: today,
+            total_seconds: 3600.0,
+        }];
+
+        let streak = calc_coding_streak(days.clone(), 60);
+        assert_eq!(streak.stats.current_streak.length, 1);
+
+        let streak = calc_coding_streak(days, u32::MAX);
+        assert_eq!(streak.stats.current_streak.length, 0);
+        assert_eq!(streak.stats.longest_streak.length, 0);
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap()
+    }
+
+    fn contributions(counts: &[i32]) -> BTreeMap<NaiveDate, i32> {
+        counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (date(i as u32 + 1), *count))
+            .collect()
+    }
+
+    #[test]
+    fn streak_stats_of_empty_days() {
+        let stats = calc_streak_stats(&BTreeMap::new());
+
+        assert_eq!(stats.total_contributions, 0);
+        assert_eq!(stats.first_contribution, None);
+        assert_eq!(stats.current_streak.length, 0);
+        assert_eq!(stats.longest_streak.length, 0);
+        assert_eq!(
+            fmt_streak_range(&stats.current_streak),
+            "No contributions yet"
+        );
+    }
+
+    #[test]
+    fn streak_stats_of_single_day() {
+        let stats = calc_streak_stats(&contributions(&[3]));
+
+        assert_eq!(stats.total_contributions, 3);
+        assert_eq!(stats.first_contribution, Some(date(1)));
+        assert_eq!(stats.current_streak.length, 1);
+        assert_eq!(stats.current_streak.start, Some(date(1)));
+        assert_eq!(stats.current_streak.end, Some(date(1)));
+        assert_eq!(stats.longest_streak.length, 1);
+    }
+
+    #[test]
+    fn streak_stats_with_gaps() {
+        let stats = calc_streak_stats(&contributions(&[0, 1, 2, 3, 0, 1, 1, 0, 5, 1]));
+
+        assert_eq!(stats.total_contributions, 14);
+        assert_eq!(stats.first_contribution, Some(date(2)));
+        assert_eq!(stats.longest_streak.length, 3);
+        assert_eq!(stats.longest_streak.start, Some(date(2)));
+        assert_eq!(stats.longest_streak.end, Some(date(4)));
+        assert_eq!(stats.current_streak.length, 2);
+        assert_eq!(stats.current_streak.start, Some(date(9)));
+        assert_eq!(stats.current_streak.end, Some(date(10)));
+    }
+
+    #[test]
+    fn streak_stats_with_missing_dates() {
+        let days = BTreeMap::from([(date(1), 1), (date(2), 1), (date(4), 1)]);
+        let stats = calc_streak_stats(&days);
+
+        assert_eq!(stats.longest_streak.length, 2);
+        assert_eq!(stats.current_streak.length, 1);
+        assert_eq!(stats.current_streak.start, Some(date(4)));
+    }
+
+    #[test]
+    fn today_without_contributions_keeps_current_streak() {
+        let stats = calc_streak_stats(&contributions(&[1, 1, 1, 0]));
+
+        assert_eq!(stats.current_streak.length, 3);
+        assert_eq!(stats.current_streak.start, Some(date(1)));
+        assert_eq!(stats.current_streak.end, Some(date(3)));
+
+        // two empty days in a row break it
+        let stats = calc_streak_stats(&contributions(&[1, 1, 1, 0, 0]));
+        assert_eq!(stats.current_streak.length, 0);
+        assert_eq!(stats.longest_streak.length, 3);
+    }
+
+    #[test]
+    fn longest_streak_keeps_first_of_equal_streaks() {
+        let stats = calc_streak_stats(&contributions(&[1, 1, 0, 2, 2]));
+
+        assert_eq!(stats.longest_streak.length, 2);
+        assert_eq!(stats.longest_streak.start, Some(date(1)));
+        assert_eq!(stats.current_streak.start, Some(date(4)));
+    }
+}
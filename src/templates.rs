@@ -1,18 +1,65 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::Duration;
+
 use askama::Template;
 use axum::{
-    http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    body::{self, Body, Bytes, HttpBody},
+    extract::{Query, Request, State},
+    http::{
+        HeaderMap, HeaderValue, Method, StatusCode,
+        header::{ACCEPT, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH, VARY},
+    },
+    middleware::Next,
+    response::{Html, IntoResponse, Json, Response},
 };
+use moka::future::Cache;
+use serde::{Deserialize, Serialize};
+
+use crate::prepared_templates::PreparedTemplate;
+use crate::utils::cache::Cached;
+use crate::utils::png::{self, MAX_SCALE, MIN_SCALE, SCALE_STEP};
+
+// max size of rendered card to rasterize or hash
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+const ETAG_CONTENT_TYPES: [&str; 4] = [
+    "image/svg+xml",
+    "image/png",
+    "application/json",
+    "text/html",
+];
+// errors mustn't be cached by CDNs and github camo as a real card
+pub const ERROR_CACHE_CONTROL: &str = "no-store";
+
+pub fn get_cache_control(max_age: Duration) -> String {
+    format!("public, max-age={}", max_age.as_secs())
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub enum Format {
+    #[serde(rename = "svg")]
+    Svg,
+    #[serde(rename = "png")]
+    Png,
+    #[serde(rename = "json")]
+    Json,
+}
+
+#[derive(Deserialize)]
+pub struct FormatParams {
+    format: Option<Format>,
+    scale: Option<f32>,
+}
 
 // global error template
 #[derive(Template)]
-#[template(path = "error.html")]
+#[template(path = "error.html", escape = "svg")]
 pub struct ErrorTemplate {
     pub first_line: &'static str,
     pub second_line: &'static str,
 }
 
-pub struct SVGTemplate<T>(pub T);
+// card with max-age of its cached data
+pub struct SVGTemplate<T>(pub T, pub Duration);
 
 impl<T> IntoResponse for SVGTemplate<T>
 where
@@ -23,10 +70,18 @@ where
             Ok(html) => {
                 let body = Html(html);
 
-                ([("content-type", "image/svg+xml; charset=utf-8")], body).into_response()
+                (
+                    [
+                        (CONTENT_TYPE, "image/svg+xml; charset=utf-8".to_string()),
+                        (CACHE_CONTROL, get_cache_control(self.1)),
+                    ],
+                    body,
+                )
+                    .into_response()
             }
             Err(err) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
+                [(CACHE_CONTROL, ERROR_CACHE_CONTROL)],
                 format!("Failed to render template. Error: {err}"),
             )
                 .into_response(),
@@ -34,6 +89,23 @@ where
     }
 }
 
+// card data for `format=json`, errors are returned as `{"error": {"code", "message"}}`
+pub struct JsonData<T>(pub Result<Cached<T>, PreparedTemplate>);
+
+impl<T> IntoResponse for JsonData<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response {
+        match self.0 {
+            Ok(Cached { data, max_age }) => {
+                ([(CACHE_CONTROL, get_cache_control(max_age))], Json(data)).into_response()
+            }
+            Err(err) => err.render_json(),
+        }
+    }
+}
+
 pub struct HtmlTemplate<T>(pub T);
 
 impl<T> IntoResponse for HtmlTemplate<T>
@@ -51,3 +123,244 @@ where
         }
     }
 }
+
+// png requested explicitly or client doesn't accept svg
+fn is_png_requested(format: Option<&Format>, headers: &HeaderMap) -> bool {
+    if let Some(format) = format {
+        return *format == Format::Png;
+    }
+
+    headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("image/png") && !accept.contains("image/svg+xml"))
+}
+
+// body of unknown or too large size is passed through as is, instead of failing to read it
+fn is_body_readable(res: &Response) -> bool {
+    let content_length = res
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|content_length| content_length.to_str().ok())
+        .and_then(|content_length| content_length.parse::<u64>().ok());
+    content_length
+        .or(res.body().size_hint().upper())
+        .is_some_and(|size| size <= MAX_BODY_SIZE as u64)
+}
+
+fn is_svg_response(res: &Response) -> bool {
+    res.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("image/svg+xml"))
+}
+
+// rasterizes svg cards to png for `format=png` or `Accept: image/png`
+pub async fn render_format(
+    State(png_cache): State<Cache<String, Bytes>>,
+    Query(params): Query<FormatParams>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let mut res = if is_png_requested(params.format.as_ref(), req.headers()) {
+        let res = next.run(req).await;
+        render_png(png_cache, params.scale, res).await
+    } else {
+        next.run(req).await
+    };
+
+    // svg and png are served from one url, so caches must key them by Accept
+    res.headers_mut()
+        .insert(VARY, HeaderValue::from_static("Accept"));
+    res
+}
+
+// one of 1, 1.5, 2, ... 4
+fn get_scale(scale: Option<f32>) -> f32 {
+    let scale = scale
+        .filter(|scale| scale.is_finite())
+        .unwrap_or(MIN_SCALE);
+    ((scale / SCALE_STEP).round() * SCALE_STEP).clamp(MIN_SCALE, MAX_SCALE)
+}
+
+// other responses than svg card are passed as is
+async fn render_png(
+    png_cache: Cache<String, Bytes>,
+    scale: Option<f32>,
+    res: Response,
+) -> Response {
+    if !is_svg_response(&res) || !is_body_readable(&res) {
+        return res;
+    }
+
+    let scale = get_scale(scale);
+    let (mut parts, res_body) = res.into_parts();
+    let svg = match body::to_bytes(res_body, MAX_BODY_SIZE).await {
+        Ok(svg) => svg,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let mut hasher = DefaultHasher::new();
+    svg.hash(&mut hasher);
+    let cache_key = format!("png:{:x}:{scale}", hasher.finish());
+    let png_body = match png_cache.get(&cache_key).await {
+        Some(cached) => cached,
+        None => {
+            let rasterized = tokio::task::spawn_blocking(move || {
+                png::rasterize(&String::from_utf8_lossy(&svg), scale)
+            })
+            .await;
+            match rasterized {
+                Ok(Ok(png_data)) => {
+                    let png_data = Bytes::from(png_data);
+                    png_cache.insert(cache_key, png_data.clone()).await;
+                    png_data
+                }
+                Ok(Err(err)) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        [(CACHE_CONTROL, ERROR_CACHE_CONTROL)],
+                        format!("Failed to render png. Error: {err}"),
+                    )
+                        .into_response();
+                }
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }
+    };
+
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+    Response::from_parts(parts, Body::from(png_body))
+}
+
+fn is_etag_supported(res: &Response) -> bool {
+    res.status() == StatusCode::OK
+        && !res.headers().contains_key(ETAG)
+        && res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| {
+                ETAG_CONTENT_TYPES
+                    .iter()
+                    .any(|supported| content_type.starts_with(supported))
+            })
+}
+
+// If-None-Match uses weak comparison, so W/ prefix is ignored
+fn is_etag_matched(if_none_match: Option<&HeaderValue>, etag: &str) -> bool {
+    let Some(if_none_match) = if_none_match.and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+// strong etag from rendered body, responds 304 for matched If-None-Match
+pub async fn with_etag(req: Request, next: Next) -> Response {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return next.run(req).await;
+    }
+
+    let if_none_match = req.headers().get(IF_NONE_MATCH).cloned();
+    let res = next.run(req).await;
+    if !is_etag_supported(&res) || !is_body_readable(&res) {
+        return res;
+    }
+
+    let (mut parts, res_body) = res.into_parts();
+    let res_bytes = match body::to_bytes(res_body, MAX_BODY_SIZE).await {
+        Ok(res_bytes) => res_bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let mut hasher = DefaultHasher::new();
+    res_bytes.hash(&mut hasher);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+    let etag_value = HeaderValue::from_str(&etag).unwrap();
+    if is_etag_matched(if_none_match.as_ref(), &etag) {
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        not_modified.headers_mut().insert(ETAG, etag_value);
+        for header in [CACHE_CONTROL, VARY] {
+            if let Some(value) = parts.headers.get(&header) {
+                not_modified.headers_mut().insert(header, value.clone());
+            }
+        }
+
+        return not_modified;
+    }
+
+    parts.headers.insert(ETAG, etag_value);
+    Response::from_parts(parts, Body::from(res_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, middleware, routing::get};
+    use tower::ServiceExt;
+
+    fn svg_response(size: usize) -> Response {
+        let svg = format!("<svg>{}</svg>", " ".repeat(size));
+        ([(CONTENT_TYPE, "image/svg+xml; charset=utf-8")], svg).into_response()
+    }
+
+    async fn send(app: Router, uri: &str) -> Response {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app.oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn etag_is_added_to_small_body() {
+        let app = Router::new()
+            .route("/", get(|| async { svg_response(16) }))
+            .route_layer(middleware::from_fn(with_etag));
+        let res = send(app, "/").await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().contains_key(ETAG));
+    }
+
+    #[tokio::test]
+    async fn large_body_is_passed_without_etag() {
+        let app = Router::new()
+            .route("/", get(|| async { svg_response(MAX_BODY_SIZE) }))
+            .route_layer(middleware::from_fn(with_etag));
+        let res = send(app, "/").await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res.headers().contains_key(ETAG));
+        let res_body = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(res_body.len(), MAX_BODY_SIZE + "<svg></svg>".len());
+    }
+
+    #[test]
+    fn scale_is_rounded_to_steps() {
+        assert_eq!(get_scale(None), 1.0);
+        assert_eq!(get_scale(Some(f32::NAN)), 1.0);
+        assert_eq!(get_scale(Some(1.0001)), 1.0);
+        assert_eq!(get_scale(Some(1.3)), 1.5);
+        assert_eq!(get_scale(Some(2.2)), 2.0);
+        assert_eq!(get_scale(Some(0.1)), 1.0);
+        assert_eq!(get_scale(Some(100.0)), 4.0);
+    }
+
+    #[tokio::test]
+    async fn large_svg_isnt_rasterized() {
+        let png_cache: Cache<String, Bytes> = Cache::new(1);
+        let app = Router::new()
+            .route("/", get(|| async { svg_response(MAX_BODY_SIZE) }))
+            .route_layer(middleware::from_fn_with_state(png_cache, render_format));
+        let res = send(app, "/?format=png").await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(is_svg_response(&res));
+        assert_eq!(res.headers().get(VARY).unwrap(), "Accept");
+    }
+}
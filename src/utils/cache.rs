@@ -0,0 +1,426 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use moka::{Expiry, future::Cache};
+use redb::{Database, ReadableDatabase, ReadableTable, ReadableTableMetadata, TableDefinition};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::data::config::CONFIG;
+
+const DISK_CACHE_FILE: &str = "cache.redb";
+// how often outdated entries are removed from disk cache
+const DISK_CACHE_SWEEP_INTERVAL: Duration = Duration::from_secs(600);
+// key -> (expires at in unix seconds, value)
+const DISK_CACHE_TABLE: TableDefinition<&str, (u64, &str)> = TableDefinition::new("cache");
+
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub value: String,
+    // unix seconds, entry is stale after it
+    pub expires_at: u64,
+}
+
+impl CacheEntry {
+    // stale entries are kept for CACHE_MAX_STALE
+    pub fn time_to_live(&self) -> Duration {
+        let removed_at = self.expires_at + CONFIG.cache_max_stale.as_secs();
+        Duration::from_secs(removed_at.saturating_sub(get_unix_now()))
+    }
+
+    // how long clients may cache the card, stale entries are revalidated at once
+    pub fn max_age(&self) -> Duration {
+        Duration::from_secs(self.expires_at.saturating_sub(get_unix_now()))
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.expires_at <= get_unix_now()
+    }
+
+    pub fn is_outdated(&self) -> bool {
+        self.time_to_live().is_zero()
+    }
+}
+
+// cached data with remaining lifetime of its entry
+#[derive(Debug)]
+pub struct Cached<T> {
+    pub data: T,
+    pub max_age: Duration,
+}
+
+impl<T> Cached<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Cached<U> {
+        Cached {
+            data: f(self.data),
+            max_age: self.max_age,
+        }
+    }
+
+    pub fn try_map<U, E>(self, f: impl FnOnce(T) -> Result<U, E>) -> Result<Cached<U>, E> {
+        Ok(Cached {
+            data: f(self.data)?,
+            max_age: self.max_age,
+        })
+    }
+}
+
+// persistent backend behind in-memory cache
+pub trait CacheStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<CacheEntry>;
+    fn insert(&self, key: &str, entry: &CacheEntry);
+}
+
+pub struct DiskStore {
+    db: Database,
+    // max count of entries kept after sweep
+    capacity: u64,
+}
+
+impl DiskStore {
+    pub fn open(dir: &str, capacity: u64) -> Result<Self, redb::Error> {
+        fs::create_dir_all(dir).map_err(redb::Error::Io)?;
+        let db = Database::create(Path::new(dir).join(DISK_CACHE_FILE))?;
+        let store = Self { db, capacity };
+
+        // drop entries outdated while service was stopped
+        store.sweep()?;
+
+        Ok(store)
+    }
+
+    // removes outdated entries, then entries expiring first over capacity
+    pub fn sweep(&self) -> Result<(), redb::Error> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(DISK_CACHE_TABLE)?;
+            table.retain(|_, (expires_at, value)| {
+                let entry = CacheEntry {
+                    value: value.to_string(),
+                    expires_at,
+                };
+                !entry.is_outdated()
+            })?;
+
+            let excess = table.len()?.saturating_sub(self.capacity) as usize;
+            if excess > 0 {
+                let mut expirations = Vec::new();
+                for item in table.iter()? {
+                    let (key, data) = item?;
+                    expirations.push((data.value().0, key.value().to_string()));
+                }
+
+                expirations.sort_unstable();
+                for (_, key) in expirations.into_iter().take(excess) {
+                    table.remove(key.as_str())?;
+                }
+            }
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    // sweeps disk cache in background, so it doesn't grow without limit between restarts
+    pub fn spawn_sweeper(store: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DISK_CACHE_SWEEP_INTERVAL);
+            // first tick is immediate, but store is already swept on open
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let sweep_store = store.clone();
+                match tokio::task::spawn_blocking(move || sweep_store.sweep()).await {
+                    Ok(Err(err)) => println!("Failed to sweep disk cache: {err}"),
+                    Err(err) => println!("Failed to sweep disk cache: {err}"),
+                    Ok(Ok(())) => {}
+                }
+            }
+        });
+    }
+
+    fn get_intl(&self, key: &str) -> Result<Option<CacheEntry>, redb::Error> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(DISK_CACHE_TABLE)?;
+        let entry = table.get(key)?.map(|data| {
+            let (expires_at, value) = data.value();
+            CacheEntry {
+                value: value.to_string(),
+                expires_at,
+            }
+        });
+
+        Ok(entry)
+    }
+
+    fn insert_intl(&self, key: &str, entry: &CacheEntry) -> Result<(), redb::Error> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(DISK_CACHE_TABLE)?;
+            table.insert(key, (entry.expires_at, entry.value.as_str()))?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+}
+
+impl CacheStore for DiskStore {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        match self.get_intl(key) {
+            Ok(entry) => entry,
+            Err(err) => {
+                println!("Failed to read {key} from disk cache: {err}");
+                None
+            }
+        }
+    }
+
+    fn insert(&self, key: &str, entry: &CacheEntry) {
+        if let Err(err) = self.insert_intl(key, entry) {
+            println!("Failed to write {key} to disk cache: {err}");
+        }
+    }
+}
+
+fn get_unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// card by cache key, e.g. github:activity:{username}:{period} -> activity
+pub fn get_cache_card(key: &str) -> Option<&'static str> {
+    let mut segments = key.split(':');
+    let card = match (segments.next()?, segments.next()?) {
+        ("github" | "wakatime" | "wakapi", "langs") => "top_langs",
+        ("wakatime", "editors" | "os" | "categories") => "top_langs",
+        ("github", "activity") => "activity",
+        ("github", "repo" | "gist") => "pin_github",
+        ("huggingface", _) => "pin_huggingface",
+        ("github", "streak") | ("wakatime", "days") => "streak",
+        ("github", "stats") => "stats",
+        _ => return None,
+    };
+
+    Some(card)
+}
+
+pub fn get_cache_ttl(key: &str) -> Duration {
+    get_cache_card(key)
+        .and_then(|card| CONFIG.card_cache_ttls.get(card))
+        .copied()
+        .unwrap_or(CONFIG.cache_ttl)
+}
+
+// per-entry ttl, so one cache holds cards with different lifetimes
+pub struct CardExpiry;
+
+impl Expiry<String, CacheEntry> for CardExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &CacheEntry,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(value.time_to_live())
+    }
+
+    // refetched data lives full ttl again
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        value: &CacheEntry,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(value.time_to_live())
+    }
+}
+
+fn create_entry<T: Serialize>(key: &str, data: &T) -> CacheEntry {
+    CacheEntry {
+        value: serde_json::to_string(data).unwrap(),
+        expires_at: get_unix_now() + get_cache_ttl(key).as_secs(),
+    }
+}
+
+// in-memory cache with optional persistent store, entries from store are kept in memory
+#[derive(Clone)]
+pub struct CardCache {
+    memory: Cache<String, CacheEntry>,
+    store: Option<Arc<dyn CacheStore>>,
+    // keys of stale entries refreshed in background
+    refreshing: Arc<Mutex<HashSet<String>>>,
+}
+
+impl CardCache {
+    pub fn new(store: Option<Arc<dyn CacheStore>>) -> Self {
+        let memory = Cache::builder()
+            .expire_after(CardExpiry)
+            .max_capacity(CONFIG.cache_capacity)
+            .build();
+
+        Self {
+            memory,
+            store,
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    // fresh data is returned from cache, stale data is returned at once and refreshed in background.
+    // on cache miss concurrent calls for one key wait for a single fetch
+    pub async fn get_or_fetch<T, E, F>(&self, key: String, fetch: F) -> Result<Cached<T>, E>
+    where
+        T: Serialize + DeserializeOwned + Send + 'static,
+        E: Clone + Send + Sync + 'static,
+        F: Future<Output = Result<T, E>> + Send + 'static,
+    {
+        let entry = match self.memory.get(&key).await {
+            Some(entry) => {
+                if entry.is_stale() {
+                    self.refresh(key, fetch);
+                }
+
+                entry
+            }
+            None => self
+                .memory
+                .try_get_with(key.clone(), self.load::<T, E, F>(key, fetch))
+                .await
+                .map_err(|err| (*err).clone())?,
+        };
+
+        // entries in memory are always written by current service version
+        Ok(Cached {
+            data: serde_json::from_str(&entry.value).unwrap(),
+            max_age: entry.max_age(),
+        })
+    }
+
+    // entry from persistent store or fetched data
+    async fn load<T, E, F>(&self, key: String, fetch: F) -> Result<CacheEntry, E>
+    where
+        T: Serialize + DeserializeOwned + Send + 'static,
+        E: Send + 'static,
+        F: Future<Output = Result<T, E>> + Send + 'static,
+    {
+        if let Some(entry) = self.get_stored::<T>(&key).await {
+            if entry.is_stale() {
+                self.refresh(key, fetch);
+            }
+
+            return Ok(entry);
+        }
+
+        let data = fetch.await?;
+        let entry = create_entry(&key, &data);
+        self.store_entry(&key, &entry);
+        Ok(entry)
+    }
+
+    fn refresh<T, E, F>(&self, key: String, fetch: F)
+    where
+        T: Serialize + Send + 'static,
+        E: Send + 'static,
+        F: Future<Output = Result<T, E>> + Send + 'static,
+    {
+        if !self.refreshing.lock().unwrap().insert(key.clone()) {
+            return;
+        }
+
+        let cache = self.clone();
+        tokio::spawn(async move {
+            // on upstream error stale data is served until CACHE_MAX_STALE
+            if let Ok(data) = fetch.await {
+                let entry = create_entry(&key, &data);
+                cache.store_entry(&key, &entry);
+                cache.memory.insert(key.clone(), entry).await;
+            }
+
+            cache.refreshing.lock().unwrap().remove(&key);
+        });
+    }
+
+    async fn get_stored<T: DeserializeOwned>(&self, key: &str) -> Option<CacheEntry> {
+        let store = self.store.clone()?;
+        let store_key = key.to_string();
+        tokio::task::spawn_blocking(move || store.get(&store_key))
+            .await
+            .ok()
+            .flatten()
+            .filter(|entry| !entry.is_outdated())
+            // entries of old service versions are refetched
+            .filter(|entry| serde_json::from_str::<T>(&entry.value).is_ok())
+    }
+
+    fn store_entry(&self, key: &str, entry: &CacheEntry) {
+        if let Some(store) = self.store.clone() {
+            let store_key = key.to_string();
+            let store_entry = entry.clone();
+            // don't wait for disk write
+            tokio::task::spawn_blocking(move || store.insert(&store_key, &store_entry));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_entry(expires_at: u64) -> CacheEntry {
+        CacheEntry {
+            value: "{}".to_string(),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn max_age_is_remaining_fresh_lifetime() {
+        let now = get_unix_now();
+
+        let max_age = create_test_entry(now + 60).max_age().as_secs();
+        assert!((59..=60).contains(&max_age));
+        assert_eq!(create_test_entry(now - 60).max_age(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn fetched_data_has_card_max_age() {
+        let cache = CardCache::new(None);
+        let key = "github:stats:octocat".to_string();
+        let card_ttl = get_cache_ttl(&key);
+        let cached = cache
+            .get_or_fetch(key, async { Ok::<_, ()>(42) })
+            .await
+            .unwrap();
+
+        assert_eq!(cached.data, 42);
+        assert!(cached.max_age <= card_ttl);
+        assert!(cached.max_age + Duration::from_secs(1) >= card_ttl);
+    }
+
+    #[test]
+    fn sweep_removes_outdated_and_over_capacity_entries() {
+        let dir = std::env::temp_dir().join(format!("stats-cards-sweep-{}", std::process::id()));
+        let store = DiskStore::open(dir.to_str().unwrap(), 2).unwrap();
+        let now = get_unix_now();
+        let outdated_at = now - CONFIG.cache_max_stale.as_secs() - 1;
+
+        store.insert("outdated", &create_test_entry(outdated_at));
+        store.insert("stale", &create_test_entry(now - 1));
+        store.insert("fresh", &create_test_entry(now + 60));
+        store.insert("freshest", &create_test_entry(now + 120));
+        store.sweep().unwrap();
+
+        assert!(store.get("outdated").is_none());
+        assert!(store.get("stale").is_none());
+        assert!(store.get("fresh").is_some());
+        assert!(store.get("freshest").is_some());
+
+        drop(store);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
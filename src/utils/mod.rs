@@ -1,2 +1,5 @@
+pub mod cache;
+pub mod png;
 pub mod svg;
+#[allow(clippy::module_inception)]
 pub mod utils;
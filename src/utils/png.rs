@@ -0,0 +1,56 @@
+use std::fs;
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{self, fontdb},
+};
+
+use crate::utils::svg::{FONT_PATH, get_fallback_font_paths};
+
+pub const MIN_SCALE: f32 = 1.0;
+pub const MAX_SCALE: f32 = 4.0;
+// scale is rounded to steps, so arbitrary floats don't create new cache entries
+pub const SCALE_STEP: f32 = 0.5;
+
+lazy_static! {
+    static ref FONT_DB: Arc<fontdb::Database> = Arc::new(load_font_db());
+}
+
+// same fonts as for text measuring, system fonts aren't loaded
+fn load_font_db() -> fontdb::Database {
+    let mut font_db = fontdb::Database::new();
+    font_db.load_font_data(FONT_PATH.to_vec());
+    for path in get_fallback_font_paths() {
+        match fs::read(&path) {
+            Ok(font_data) => font_db.load_font_data(font_data),
+            Err(err) => println!("Failed to load fallback font {}: {err}", path.display()),
+        }
+    }
+
+    font_db.set_sans_serif_family("Segoe UI");
+    font_db
+}
+
+pub fn rasterize(svg: &str, scale: f32) -> Result<Vec<u8>, String> {
+    let options = usvg::Options {
+        fontdb: FONT_DB.clone(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|err| err.to_string())?;
+
+    let size = tree
+        .size()
+        .to_int_size()
+        .scale_by(scale)
+        .ok_or("Invalid image size")?;
+    let mut pixmap = Pixmap::new(size.width(), size.height()).ok_or("Failed to create pixmap")?;
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    pixmap.encode_png().map_err(|err| err.to_string())
+}
@@ -1,30 +1,189 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use askama::filters::Escaper;
 use fontdue::{Font, FontSettings};
+use lazy_static::lazy_static;
+
+use crate::data::config::CONFIG;
 
-const FONT_PATH: &[u8] = include_bytes!("../../fonts/segoeui.ttf") as &[u8];
+pub const FONT_PATH: &[u8] = include_bytes!("../../fonts/segoeui.ttf") as &[u8];
+const FONT_EXTENSIONS: [&str; 3] = ["ttf", "otf", "ttc"];
 const MAX_LINES: usize = 2;
 
-pub fn wrap_text(text: &String, font_size: f32, max_width: usize) -> Vec<String> {
+lazy_static! {
+    static ref FONT: Font = Font::from_bytes(FONT_PATH, FontSettings::default()).unwrap();
+    static ref FALLBACK_FONTS: Vec<Font> = load_fallback_fonts();
+    // (char, font size bits) -> advance width
+    static ref ADVANCES: RwLock<HashMap<(char, u32), f32>> = RwLock::new(HashMap::new());
+}
+
+#[derive(Debug)]
+struct Word {
+    text: String,
+    with_space: bool,
+}
+
+// fonts are checked in filename order, e.g. 01-noto-sans-jp.ttf, 02-noto-emoji.ttf
+pub fn get_fallback_font_paths() -> Vec<PathBuf> {
+    let Some(fonts_dir) = &CONFIG.fonts_dir else {
+        return Vec::new();
+    };
+
+    let Ok(entries) = fs::read_dir(fonts_dir) else {
+        println!("Failed to read fallback fonts dir {fonts_dir}");
+        return Vec::new();
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| FONT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        })
+        .collect();
+    paths.sort();
+
+    paths
+}
+
+fn load_fallback_fonts() -> Vec<Font> {
+    get_fallback_font_paths()
+        .iter()
+        .filter_map(|path| {
+            let font_data = fs::read(path).ok()?;
+            match Font::from_bytes(font_data, FontSettings::default()) {
+                Ok(font) => Some(font),
+                Err(err) => {
+                    println!("Failed to load fallback font {}: {err}", path.display());
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// chinese, japanese and korean
+fn is_cjk_char(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF
+        | 0x2E80..=0x2FDF
+        | 0x3000..=0x30FF
+        | 0x3130..=0x318F
+        | 0x31C0..=0x31FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xAC00..=0xD7AF
+        | 0xF900..=0xFAFF
+        | 0xFF00..=0xFFEF
+        | 0x20000..=0x3134F
+    )
+}
+
+// cjk punctuation which can't start a line
+const CLOSING_PUNCT: &str = "、。〉》」』】〕〗〙〛ー！），．：；？］｝｡｣､";
+
+fn is_emoji_char(c: char) -> bool {
+    matches!(c as u32, 0x2600..=0x27BF | 0x1F000..=0x1FAFF)
+}
+
+// joiners, variation selectors and other chars that don't take space
+fn is_zero_width_char(c: char) -> bool {
+    c.is_control() || matches!(c, '\u{200B}'..='\u{200F}' | '\u{FE00}'..='\u{FE0F}' | '\u{FEFF}')
+}
+
+// cjk text has no whitespaces, so each cjk char is a separate word,
+// closing punctuation is kept with the previous char
+fn split_words(text: &str) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    for (idx, raw_word) in text.split_whitespace().enumerate() {
+        let mut with_space = idx > 0;
+        let mut current_word = String::new();
+        for c in raw_word.chars() {
+            if !is_cjk_char(c) {
+                current_word.push(c);
+                continue;
+            }
+
+            if CLOSING_PUNCT.contains(c) {
+                if !current_word.is_empty() {
+                    current_word.push(c);
+                    continue;
+                }
+
+                if !with_space && let Some(prev_word) = words.last_mut() {
+                    prev_word.text.push(c);
+                    continue;
+                }
+            }
+
+            if !current_word.is_empty() {
+                words.push(Word {
+                    text: std::mem::take(&mut current_word),
+                    with_space,
+                });
+                with_space = false;
+            }
+
+            words.push(Word {
+                text: c.to_string(),
+                with_space,
+            });
+            with_space = false;
+        }
+
+        if !current_word.is_empty() {
+            words.push(Word {
+                text: current_word,
+                with_space,
+            });
+        }
+    }
+
+    words
+}
+
+fn join_word(line: &str, word: &Word) -> String {
+    if line.is_empty() {
+        word.text.clone()
+    } else if word.with_space {
+        format!("{} {}", line, word.text)
+    } else {
+        format!("{}{}", line, word.text)
+    }
+}
+
+pub fn wrap_text(text: &str, font_size: f32, max_width: usize) -> Vec<String> {
+    wrap_text_with(text, font_size, max_width, calc_width)
+}
+
+// wrap_text with custom width measuring, e.g. uncached one in benchmarks
+pub fn wrap_text_with(
+    text: &str,
+    font_size: f32,
+    max_width: usize,
+    calc_width: impl Fn(&str, f32) -> usize,
+) -> Vec<String> {
     let mut lines = Vec::new();
     let mut current_line = String::new();
-    let mut words = text.split_whitespace().peekable();
+    let mut words = split_words(text).into_iter();
     let mut truncated = false;
 
     while let Some(word) = words.next() {
-        let next_line = if current_line.is_empty() {
-            word.to_string()
-        } else {
-            format!("{} {}", current_line, word)
-        };
-
+        let next_line = join_word(&current_line, &word);
         if calc_width(&next_line, font_size) <= max_width {
             current_line = next_line;
         } else {
             lines.push(current_line);
-            current_line = word.to_string();
+            current_line = word.text;
 
             if lines.len() == MAX_LINES - 1 {
-                while let Some(next_word) = words.next() {
-                    let test = format!("{} {}", current_line, next_word);
+                for next_word in words.by_ref() {
+                    let test = join_word(&current_line, &next_word);
                     if calc_width(&test, font_size) <= max_width {
                         current_line = test;
                     } else {
@@ -50,13 +209,228 @@ pub fn wrap_text(text: &String, font_size: f32, max_width: usize) -> Vec<String>
     lines
 }
 
+fn get_advance_width(c: char, font_size: f32) -> f32 {
+    let key = (c, font_size.to_bits());
+    if let Some(advance_width) = ADVANCES.read().unwrap().get(&key) {
+        return *advance_width;
+    }
+
+    let advance_width = find_advance_width(c, font_size);
+    ADVANCES.write().unwrap().insert(key, advance_width);
+    advance_width
+}
+
+fn find_advance_width(c: char, font_size: f32) -> f32 {
+    if is_zero_width_char(c) {
+        return 0.0;
+    }
+
+    let font = std::iter::once(&*FONT)
+        .chain(FALLBACK_FONTS.iter())
+        .find(|font| font.lookup_glyph_index(c) != 0);
+    if let Some(font) = font {
+        return font.metrics(c, font_size).advance_width;
+    }
+
+    // glyph isn't found in any font, so approximate its width
+    if is_cjk_char(c) || is_emoji_char(c) {
+        font_size
+    } else {
+        font_size / 2.0
+    }
+}
+
+fn is_primary_font_char(c: char) -> bool {
+    !is_zero_width_char(c) && FONT.lookup_glyph_index(c) != 0
+}
+
 pub fn calc_width(text: &str, font_size: f32) -> usize {
-    let font = Font::from_bytes(FONT_PATH, FontSettings::default()).unwrap();
     let mut width = 0.0;
+    // kerning pairs of primary font are applied only between its own glyphs
+    let mut prev_char: Option<char> = None;
     for c in text.chars() {
-        let (metrics, _) = font.rasterize(c, font_size);
-        width += metrics.advance_width;
+        width += get_advance_width(c, font_size);
+        if !is_primary_font_char(c) {
+            prev_char = None;
+            continue;
+        }
+
+        if let Some(prev_char) = prev_char
+            && let Some(kern) = FONT.horizontal_kern(prev_char, c, font_size)
+        {
+            width += kern;
+        }
+
+        prev_char = Some(c);
     }
 
     width.ceil() as usize
 }
+
+// measuring as it was before font and advances caching, parses the font on
+// every call, only for comparison in benchmarks
+#[cfg(test)]
+pub fn calc_width_uncached(text: &str, font_size: f32) -> usize {
+    let font = Font::from_bytes(FONT_PATH, FontSettings::default()).unwrap();
+    let width: f32 = text
+        .chars()
+        .map(|c| font.rasterize(c, font_size).0.advance_width)
+        .sum();
+    width.ceil() as usize
+}
+
+// escaper for svg templates (see askama.toml)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Xml;
+
+impl Escaper for Xml {
+    fn write_escaped_str<W: Write>(&self, mut dest: W, string: &str) -> fmt::Result {
+        for c in string.chars() {
+            self.write_escaped_char(&mut dest, c)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_escaped_char<W: Write>(&self, mut dest: W, c: char) -> fmt::Result {
+        match c {
+            '&' => dest.write_str("&amp;"),
+            '<' => dest.write_str("&lt;"),
+            '>' => dest.write_str("&gt;"),
+            '"' => dest.write_str("&quot;"),
+            '\'' => dest.write_str("&#39;"),
+            // control chars aren't allowed in xml 1.0 even as entities
+            c if !is_valid_xml_char(c) => Ok(()),
+            c => dest.write_char(c),
+        }
+    }
+}
+
+fn is_valid_xml_char(c: char) -> bool {
+    matches!(c,
+        '\t' | '\n' | '\r'
+        | '\u{20}'..='\u{D7FF}'
+        | '\u{E000}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{10FFFF}'
+    )
+}
+
+// trimmed text nodes of the svg, panics if it isn't well-formed xml
+#[cfg(test)]
+pub fn parse_svg_texts(svg: &str) -> Vec<String> {
+    let doc = roxmltree::Document::parse(svg).expect("svg should be well-formed xml");
+    doc.descendants()
+        .filter(|node| node.is_text())
+        .filter_map(|node| node.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escape(string: &str) -> String {
+        let mut dest = String::new();
+        Xml.write_escaped_str(&mut dest, string).unwrap();
+        dest
+    }
+
+    fn calc_unkerned_width(text: &str, font_size: f32) -> usize {
+        let width: f32 = text.chars().map(|c| get_advance_width(c, font_size)).sum();
+        width.ceil() as usize
+    }
+
+    #[test]
+    fn calc_width_of_known_strings() {
+        // widths measured with fonts/segoeui.ttf
+        assert_eq!(calc_width("", 13.0), 0);
+        assert_eq!(calc_width("AV", 13.0), 16);
+        assert_eq!(calc_width("To", 13.0), 14);
+        assert_eq!(calc_width("WAVE", 13.0), 34);
+        assert_eq!(calc_width("WAVE", 16.0), 42);
+        assert_eq!(calc_width("Hello, world!", 13.0), 73);
+    }
+
+    #[test]
+    fn calc_width_applies_kerning() {
+        assert!(calc_width("AV", 13.0) < calc_unkerned_width("AV", 13.0));
+        assert!(calc_width("WAVE", 13.0) < calc_unkerned_width("WAVE", 13.0));
+        // no kerning pairs
+        assert_eq!(
+            calc_width("stats-cards", 13.0),
+            calc_unkerned_width("stats-cards", 13.0)
+        );
+        // glyphs from fallback fonts aren't kerned with primary font pairs
+        assert_eq!(
+            calc_width("A日V日", 13.0),
+            calc_unkerned_width("A日V日", 13.0)
+        );
+    }
+
+    fn split_texts(text: &str) -> Vec<String> {
+        split_words(text)
+            .into_iter()
+            .map(|word| word.text)
+            .collect()
+    }
+
+    #[test]
+    fn splits_cjk_text_per_char() {
+        assert_eq!(
+            split_texts("今日は、晴れ。"),
+            vec!["今", "日", "は、", "晴", "れ。"]
+        );
+        assert_eq!(
+            split_texts("Rust製の、CLIツール。"),
+            vec!["Rust", "製", "の、", "CLI", "ツー", "ル。"]
+        );
+        assert_eq!(
+            split_texts("（ＣＬＩ）！"),
+            vec!["（", "Ｃ", "Ｌ", "Ｉ）！"]
+        );
+    }
+
+    #[test]
+    fn wraps_cjk_text() {
+        let text = "一二三四五。六七八九十";
+        let lines = wrap_text(text, 13.0, calc_width("一二三四五", 13.0));
+
+        assert_eq!(lines[0], "一二三四");
+        assert!(lines[1].starts_with("五。"));
+        assert!(text.starts_with(&lines.concat().replace("...", "")));
+    }
+
+    #[test]
+    fn wraps_mixed_text() {
+        let text = "Fast stats cards 統計カード。";
+        assert_eq!(wrap_text(text, 13.0, 1000), vec![text]);
+
+        let lines = wrap_text(text, 13.0, calc_width("Fast stats cards 統計", 13.0));
+        assert_eq!(lines, vec!["Fast stats cards 統計", "カード。"]);
+
+        let lines = wrap_text("Rust製のCLI", 13.0, calc_width("Rust製の", 13.0));
+        assert_eq!(lines, vec!["Rust製の", "CLI"]);
+    }
+
+    #[test]
+    fn escapes_markup_chars() {
+        assert_eq!(escape("<a>&\"'"), "&lt;a&gt;&amp;&quot;&#39;");
+        assert_eq!(escape("plain text"), "plain text");
+    }
+
+    #[test]
+    fn drops_invalid_xml_chars() {
+        assert_eq!(escape("a\u{1}b\u{FFFF}c"), "abc");
+        assert_eq!(escape("a\tb\nc"), "a\tb\nc");
+        assert_eq!(escape("日本語 🦀"), "日本語 🦀");
+    }
+
+    #[test]
+    fn escaped_text_round_trips() {
+        let name = "<a>&\"'\u{1}";
+        let svg = format!("<svg><title>{}</title></svg>", escape(name));
+        assert_eq!(parse_svg_texts(&svg), vec!["<a>&\"'"]);
+    }
+}